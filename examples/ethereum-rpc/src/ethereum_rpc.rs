@@ -74,8 +74,8 @@ pub enum EthereumRPC {
 }
 
 impl JsonRpcTarget for EthereumRPC {
-    fn method_name(&self) -> &'static str {
-        match self {
+    fn method_name(&self) -> Cow<'static, str> {
+        let method = match self {
             EthereumRPC::Syncing => "eth_syncing",
             EthereumRPC::ChainId => "eth_chainId",
             EthereumRPC::GasPrice => "eth_gasPrice",
@@ -90,7 +90,8 @@ impl JsonRpcTarget for EthereumRPC {
             EthereumRPC::GetCode(_, _) => "eth_getCode",
             EthereumRPC::BlobBaseFee => "eth_blobBaseFee",
             EthereumRPC::UninstallFilter(_) => "eth_uninstallFilter",
-        }
+        };
+        Cow::Borrowed(method)
     }
 
     fn params(&self) -> Vec<Value> {
@@ -3,12 +3,21 @@ mod ethereum_rpc_test {
     use ethereum_rpc::{BlockParameter, EthereumRPC};
     use reqwest_enum::jsonrpc::{JsonRpcResponse, JsonRpcResult};
     use reqwest_enum::provider::{JsonProviderType, Provider};
+    use reqwest_enum::testing::MockServer;
 
     const TEST_ADDRESS: &str = "0xee5f5c53ce2159fc6dd4b0571e86a4a390d04846";
 
+    /// A mock node answering `target` with `result`, and a provider pointed at it.
+    async fn mock_node(target: EthereumRPC, result: serde_json::Value) -> (MockServer, Provider<EthereumRPC>) {
+        let server = MockServer::start().await;
+        server.expect_rpc(target).respond_result(result).times(1);
+        let provider = server.provider();
+        (server, provider)
+    }
+
     #[tokio::test]
     async fn test_chain_id() {
-        let provider = Provider::<EthereumRPC>::default();
+        let (_server, provider) = mock_node(EthereumRPC::ChainId, "0x1".into()).await;
         let response: JsonRpcResponse<String> =
             provider.request_json(EthereumRPC::ChainId).await.unwrap();
         assert_eq!(response.result, "0x1");
@@ -16,7 +25,7 @@ mod ethereum_rpc_test {
 
     #[tokio::test]
     async fn test_gas_price() {
-        let provider = Provider::<EthereumRPC>::default();
+        let (_server, provider) = mock_node(EthereumRPC::GasPrice, "0x3b9aca00".into()).await;
         let response: JsonRpcResponse<String> =
             provider.request_json(EthereumRPC::GasPrice).await.unwrap();
         assert_ne!(response.result, "0x0");
@@ -24,7 +33,7 @@ mod ethereum_rpc_test {
 
     #[tokio::test]
     async fn test_block_number() {
-        let provider = Provider::<EthereumRPC>::default();
+        let (_server, provider) = mock_node(EthereumRPC::BlockNumber, "0x13c6f5e".into()).await;
         let response: JsonRpcResponse<String> = provider
             .request_json(EthereumRPC::BlockNumber)
             .await
@@ -34,7 +43,7 @@ mod ethereum_rpc_test {
 
     #[tokio::test]
     async fn test_get_balance() {
-        let provider = Provider::<EthereumRPC>::default();
+        let (_server, provider) = mock_node(EthereumRPC::GetBalance(TEST_ADDRESS), "0x2386f26fc10000".into()).await;
 
         let response: JsonRpcResponse<String> = provider
             .request_json(EthereumRPC::GetBalance(TEST_ADDRESS))
@@ -45,7 +54,8 @@ mod ethereum_rpc_test {
 
     #[tokio::test]
     async fn test_get_transaction_count() {
-        let provider = Provider::<EthereumRPC>::default();
+        let target = EthereumRPC::GetTransactionCount(TEST_ADDRESS, BlockParameter::Latest);
+        let (_server, provider) = mock_node(target, "0x3".into()).await;
         let response: JsonRpcResponse<String> = provider
             .request_json(EthereumRPC::GetTransactionCount(
                 TEST_ADDRESS,
@@ -58,7 +68,7 @@ mod ethereum_rpc_test {
 
     #[tokio::test]
     async fn test_syncing() {
-        let provider = Provider::<EthereumRPC>::default();
+        let (_server, provider) = mock_node(EthereumRPC::Syncing, false.into()).await;
         let response: JsonRpcResult<bool> =
             provider.request_json(EthereumRPC::Syncing).await.unwrap();
 
@@ -74,7 +84,7 @@ mod ethereum_rpc_test {
 
     #[tokio::test]
     async fn test_blob_base_fee() {
-        let provider = Provider::<EthereumRPC>::default();
+        let (_server, provider) = mock_node(EthereumRPC::BlobBaseFee, "0x1".into()).await;
         let result: JsonRpcResult<String> = provider
             .request_json(EthereumRPC::BlobBaseFee)
             .await
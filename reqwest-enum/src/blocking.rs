@@ -0,0 +1,274 @@
+//! Synchronous provider (via the `blocking` feature) for code without an async runtime,
+//! such as CLI tools and build scripts.
+use crate::{
+    error::Error,
+    provider::{EndpointFn, Provider, RequestBuilderFn},
+    target::Target,
+};
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use serde::de::DeserializeOwned;
+use std::{io::Read, time::Duration};
+
+#[cfg(feature = "jsonrpc")]
+use crate::{
+    http::HTTPBody,
+    jsonrpc::{IdGenerator, JsonRpcError, JsonRpcResult},
+    provider::parse_batch_response,
+    target::JsonRpcTarget,
+};
+
+/// Blocking counterpart of `Provider`, sending requests with a `reqwest::blocking::Client`.
+///
+/// Requests are built exactly like `Provider` builds them (URL, query, headers, auth, body,
+/// timeout and `request_fn`). Like `reqwest::blocking`, it must not be used from within an
+/// async runtime.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let provider = BlockingProvider::<HttpBin>::default();
+/// let response: serde_json::Value = provider.request_json(HttpBin::Get)?;
+/// ```
+pub struct BlockingProvider<T: Target> {
+    /// Builds requests; only its configuration is used, never its client.
+    builder: Provider<T>,
+    client: reqwest::blocking::Client,
+}
+
+impl<T: Target> std::fmt::Debug for BlockingProvider<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BlockingProvider")
+            .field("builder", &self.builder)
+            .field("client", &self.client)
+            .finish()
+    }
+}
+
+impl<T: Target> BlockingProvider<T> {
+    /// Creates a new `BlockingProvider` with optional URL, request builder customization, and timeout.
+    pub fn new(
+        endpoint_fn: Option<EndpointFn<T>>,
+        request_fn: Option<RequestBuilderFn<T>>,
+        timeout: Option<Duration>,
+    ) -> Self {
+        Self {
+            builder: Provider::new(endpoint_fn, request_fn, timeout),
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    pub fn with_client(
+        client: reqwest::blocking::Client,
+        endpoint_fn: Option<EndpointFn<T>>,
+        request_fn: Option<RequestBuilderFn<T>>,
+    ) -> Self {
+        Self {
+            builder: Provider::new(endpoint_fn, request_fn, None),
+            client,
+        }
+    }
+
+    /// Sends every request to `base_url` instead of `Target::base_url`, see `Provider::with_base_url`.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.builder = self.builder.with_base_url(base_url);
+        self
+    }
+
+    pub fn request_url(&self, target: &T) -> Result<reqwest::Url, Error> {
+        self.builder.request_url(target)
+    }
+
+    /// Builds and executes a request to `Target`, returning the raw response.
+    pub fn request(&self, target: T) -> Result<reqwest::blocking::Response, Error> {
+        let request = self.builder.request_builder(&target)?.build()?;
+        Ok(self.client.execute(into_blocking(request)?)?)
+    }
+
+    /// Request and deserialize the response body as JSON, failing on error statuses.
+    pub fn request_json<U: DeserializeOwned>(&self, target: T) -> Result<U, Error> {
+        let response = self.request(target)?.error_for_status()?;
+        Ok(response.json()?)
+    }
+}
+
+#[cfg(feature = "jsonrpc")]
+impl<T: JsonRpcTarget> BlockingProvider<T> {
+    /// Sets the `IdGenerator` used for all JSON-RPC requests, see `Provider::with_id_generator`.
+    pub fn with_id_generator<G: IdGenerator + 'static>(mut self, id_generator: G) -> Self {
+        self.builder = self.builder.with_id_generator(id_generator);
+        self
+    }
+
+    /// Sends a single JSON-RPC request.
+    pub fn call<U: DeserializeOwned>(&self, target: T) -> Result<JsonRpcResult<U>, JsonRpcError> {
+        let request = self.builder.rpc_request(&target);
+        let body = HTTPBody::from(&request).map_err(|e| JsonRpcError::parse_error(format!("Failed to serialize request: {}", e)))?;
+        let response = self.send_rpc(&target, body)?;
+        response.json::<JsonRpcResult<U>>().map_err(|e| JsonRpcError::parse_error(format!("Failed to parse JSON response: {}", e)))
+    }
+
+    /// Sends the targets as one JSON-RPC batch; results only contain entries for non-notification targets.
+    pub fn batch<U: DeserializeOwned>(&self, targets: Vec<T>) -> Result<Vec<JsonRpcResult<U>>, JsonRpcError> {
+        if targets.is_empty() {
+            return Err(JsonRpcError::invalid_request("Invalid Request"));
+        }
+
+        let requests = self.builder.rpc_requests(&targets);
+        let body = HTTPBody::from_array(&requests).map_err(|e| JsonRpcError::parse_error(format!("Failed to serialize batch request: {}", e)))?;
+        let response = self.send_rpc(&targets[0], body)?;
+        let status = response.status().as_u16();
+        let body = response.bytes()?;
+        parse_batch_response(status, &body, &requests)
+    }
+
+    fn send_rpc(&self, target: &T, body: HTTPBody) -> Result<reqwest::blocking::Response, JsonRpcError> {
+        let request = self
+            .builder
+            .request_builder(target)?
+            .body(body.inner)
+            .build()
+            .map_err(|e| JsonRpcError::internal_error(format!("Failed to build request: {}", e)))?;
+        let request = into_blocking(request)?;
+        self.client
+            .execute(request)
+            .map_err(|e| JsonRpcError::internal_error(format!("Request execution failed: {}", e)))
+    }
+}
+
+impl<T: Target> Default for BlockingProvider<T> {
+    fn default() -> Self {
+        Self::new(None, None, None)
+    }
+}
+
+/// Moves a request built by `Provider::request_builder` onto the blocking client, keeping its timeout.
+fn into_blocking(mut request: reqwest::Request) -> Result<reqwest::blocking::Request, Error> {
+    let body = match request.body_mut().take() {
+        None => None,
+        Some(body) => match body.as_bytes() {
+            Some(bytes) => Some(reqwest::blocking::Body::from(Bytes::copy_from_slice(bytes))),
+            None => Some(reqwest::blocking::Body::new(StreamReader::new(body)?)),
+        },
+    };
+
+    let mut blocking = reqwest::blocking::Request::new(request.method().clone(), request.url().clone());
+    *blocking.headers_mut() = request.headers().clone();
+    *blocking.version_mut() = request.version();
+    *blocking.timeout_mut() = request.timeout().copied();
+    *blocking.body_mut() = body;
+    Ok(blocking)
+}
+
+/// Reads a streaming `reqwest::Body` for the blocking client, which reads request bodies on the
+/// calling thread; the stream is polled there on a private current-thread runtime.
+struct StreamReader {
+    body: reqwest::Body,
+    chunk: Bytes,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl StreamReader {
+    fn new(body: reqwest::Body) -> Result<Self, Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| Error::Transport(e.into()))?;
+        Ok(Self {
+            body,
+            chunk: Bytes::new(),
+            runtime,
+        })
+    }
+}
+
+impl Read for StreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.chunk.is_empty() {
+            match self.runtime.block_on(self.body.frame()) {
+                None => return Ok(0),
+                Some(Ok(frame)) => self.chunk = frame.into_data().unwrap_or_default(),
+                Some(Err(e)) => return Err(std::io::Error::other(e)),
+            }
+        }
+        let len = buf.len().min(self.chunk.len());
+        buf[..len].copy_from_slice(&self.chunk.split_to(len));
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{self, JsonBody, TestTarget},
+        testing::MockServer,
+    };
+    use serde_json::Value;
+
+    fn echo(url: String, value: u64) -> TestTarget {
+        TestTarget::post(url, "/")
+            .with_bearer("token")
+            .with_json(serde_json::json!({ "value": value }))
+            .with_rpc("echo", vec![Value::from(value)])
+    }
+
+    /// Echoes a plain body back; answers JSON-RPC calls (single or batched) with their first param.
+    fn echo_server() -> (tokio::runtime::Runtime, MockServer) {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(test_util::serve(|request| {
+            let answer = |call: &Value| serde_json::json!({ "jsonrpc": "2.0", "id": call["id"], "result": call["params"][0] });
+            let body = match request.json() {
+                Value::Array(calls) => Value::Array(calls.iter().map(answer).collect()),
+                call if call.get("method").is_some() => answer(&call),
+                plain => plain,
+            };
+            (200, body.to_string())
+        }));
+        (runtime, server)
+    }
+
+    #[test]
+    fn test_request_json() {
+        let (_runtime, server) = echo_server();
+        let provider = BlockingProvider::<TestTarget>::default();
+        let body: Value = provider.request_json(echo(server.url(), 7)).unwrap();
+        assert_eq!(body, serde_json::json!({ "value": 7 }));
+    }
+
+    #[test]
+    fn test_streaming_request_body() {
+        let (_runtime, server) = echo_server();
+        let provider = BlockingProvider::<TestTarget>::new(
+            None,
+            Some(Box::new(|builder: &crate::provider::ProviderRequestBuilder, _: &TestTarget| {
+                builder.try_clone().unwrap().body(reqwest::Body::wrap(String::from(r#"{"value":9}"#)))
+            })),
+            None,
+        );
+        let body: Value = provider.request_json(TestTarget::post(server.url(), "/")).unwrap();
+        assert_eq!(body, serde_json::json!({ "value": 9 }));
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[test]
+    fn test_call_and_batch() {
+        let (_runtime, server) = echo_server();
+        let provider = BlockingProvider::<TestTarget>::default();
+
+        let result: JsonRpcResult<u64> = provider.call(echo(server.url(), 1)).unwrap();
+        assert!(matches!(result, JsonRpcResult::Value(response) if response.result == 1));
+
+        let targets = (2..5).map(|value| echo(server.url(), value)).collect();
+        let results = provider.batch::<u64>(targets).unwrap();
+        let values = results
+            .into_iter()
+            .map(|result| match result {
+                JsonRpcResult::Value(response) => response.result,
+                JsonRpcResult::Error(response) => panic!("{}", response.error),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![2, 3, 4]);
+        assert_eq!(server.received_requests().len(), 2);
+    }
+}
@@ -0,0 +1,357 @@
+//! Fault injection (via the `testing` feature) to exercise retry, failover and fallback logic
+//! against controlled failures.
+use crate::{
+    error::Error,
+    http::HTTPResponse,
+    provider::Provider,
+    target::Target,
+    transport::{Body, Transport},
+};
+use bytes::Bytes;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[cfg(feature = "jsonrpc")]
+use crate::jsonrpc::JsonRpcError;
+
+/// A failure injected into a request.
+#[derive(Debug, Clone)]
+pub enum Fault {
+    /// Delays the request before sending it.
+    Latency(Duration),
+    /// Fails without sending, like a refused connection (`Error::Transport`).
+    ConnectionError,
+    /// Waits for the request's timeout (if any), then fails with a timed out `Error::Transport`.
+    Timeout,
+    /// Answers with this status and an empty body without sending the request.
+    Status(u16),
+    /// Sends the request and cuts the response body in half.
+    TruncatedBody,
+    /// Sends the request and replaces the response body with invalid JSON.
+    MalformedJson,
+    /// Sends the request and replaces each call's result in a JSON-RPC response (single or batch)
+    /// with `error`, each with probability `rate`, keeping ids.
+    #[cfg(feature = "jsonrpc")]
+    RpcError { error: JsonRpcError, rate: f64 },
+}
+
+type RequestMatcher = dyn Fn(&http::Request<Bytes>) -> bool + Send + Sync;
+
+struct Rule {
+    matcher: Option<Box<RequestMatcher>>,
+    probability: f64,
+    fault: Fault,
+}
+
+/// Fault injection rules with a seeded random number generator, so failing runs can be replayed.
+///
+/// Every rule matching a request is rolled independently, in order: latency adds up, the first
+/// failing fault (connection error, timeout, status) ends the request, and body faults are applied
+/// to the response.
+///
+/// ```rust,ignore
+/// let chaos = ChaosLayer::new(42)
+///     .with_fault(0.1, Fault::Latency(Duration::from_millis(200)))
+///     .with_fault(0.05, Fault::ConnectionError)
+///     .with_target_fault(&HttpBin::Post, 1.0, Fault::Status(503));
+/// let provider = Provider::<HttpBin>::default().with_chaos(chaos);
+/// ```
+pub struct ChaosLayer {
+    rules: Vec<Rule>,
+    rng: Mutex<XorShift>,
+}
+
+impl std::fmt::Debug for ChaosLayer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChaosLayer")
+            .field("rules", &self.rules.iter().map(|rule| (rule.probability, &rule.fault)).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ChaosLayer {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rules: Vec::new(),
+            rng: Mutex::new(XorShift::new(seed)),
+        }
+    }
+
+    /// Injects `fault` into any request with `probability` (0.0 - 1.0).
+    pub fn with_fault(mut self, probability: f64, fault: Fault) -> Self {
+        self.rules.push(Rule {
+            matcher: None,
+            probability,
+            fault,
+        });
+        self
+    }
+
+    /// Injects `fault` with `probability` into requests for `target`, recognized by method and path
+    /// (whatever the base URL), e.g. to fail one endpoint only.
+    pub fn with_target_fault<T: Target>(self, target: &T, probability: f64, fault: Fault) -> Self {
+        let method = http::Method::from(target.method());
+        let path = target.path();
+        let path = path.split('?').next().unwrap_or_default().to_string();
+        self.with_fault_when(probability, fault, move |request| {
+            request.method() == method && request.uri().path().ends_with(&path)
+        })
+    }
+
+    /// Injects `fault` with `probability` into requests accepted by `matcher`.
+    pub fn with_fault_when<F>(mut self, probability: f64, fault: Fault, matcher: F) -> Self
+    where
+        F: Fn(&http::Request<Bytes>) -> bool + Send + Sync + 'static,
+    {
+        self.rules.push(Rule {
+            matcher: Some(Box::new(matcher)),
+            probability,
+            fault,
+        });
+        self
+    }
+
+    /// The faults to inject into `request`, in rule order.
+    fn roll(&self, request: &http::Request<Bytes>) -> Vec<Fault> {
+        let mut rng = self.rng.lock().unwrap();
+        self.rules
+            .iter()
+            .filter(|rule| rule.matcher.as_ref().is_none_or(|matcher| matcher(request)))
+            .filter(|rule| rng.chance(rule.probability))
+            .map(|rule| rule.fault.clone())
+            .collect()
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    fn chance(&self, probability: f64) -> bool {
+        self.rng.lock().unwrap().chance(probability)
+    }
+}
+
+/// A `Transport` injecting the faults of a `ChaosLayer` around another transport.
+#[derive(Debug)]
+pub struct ChaosTransport<X> {
+    inner: Arc<X>,
+    layer: ChaosLayer,
+}
+
+impl<X: Transport> ChaosTransport<X> {
+    pub fn new(inner: X, layer: ChaosLayer) -> Self {
+        Self {
+            inner: Arc::new(inner),
+            layer,
+        }
+    }
+}
+
+impl<X: Transport> Transport for ChaosTransport<X> {
+    async fn execute(&self, request: http::Request<Bytes>) -> Result<http::Response<Body>, Error> {
+        let faults = self.layer.roll(&request);
+
+        let latency = faults
+            .iter()
+            .filter_map(|fault| match fault {
+                Fault::Latency(delay) => Some(*delay),
+                _ => None,
+            })
+            .sum::<Duration>();
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+
+        for fault in &faults {
+            match fault {
+                Fault::ConnectionError => {
+                    let error = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "chaos: connection refused");
+                    return Err(Error::Transport(Box::new(error)));
+                }
+                Fault::Timeout => {
+                    let timeout = reqwest::Request::try_from(request).ok().and_then(|request| request.timeout().copied());
+                    if let Some(timeout) = timeout {
+                        tokio::time::sleep(timeout).await;
+                    }
+                    let error = std::io::Error::new(std::io::ErrorKind::TimedOut, "chaos: request timed out");
+                    return Err(Error::Transport(Box::new(error)));
+                }
+                Fault::Status(status) => {
+                    let mut response = http::Response::new(Body::from(Bytes::new()));
+                    *response.status_mut() = http::StatusCode::from_u16(*status).map_err(|e| Error::Transport(e.into()))?;
+                    return Ok(response);
+                }
+                _ => {}
+            }
+        }
+
+        let response = self.inner.execute(request).await?;
+        if !faults.iter().any(Fault::alters_body) {
+            return Ok(response);
+        }
+
+        let (mut parts, body) = response.into_parts();
+        parts.headers.remove(http::header::CONTENT_LENGTH);
+        let mut body = HTTPResponse::from(http::Response::new(body)).bytes().await?;
+        for fault in &faults {
+            body = match fault {
+                Fault::TruncatedBody => body.slice(..body.len() / 2),
+                Fault::MalformedJson => Bytes::from_static(b"{\"jsonrpc\": \"2.0\", \"result\": "),
+                #[cfg(feature = "jsonrpc")]
+                Fault::RpcError { error, rate } => self.replace_results(body, error, *rate),
+                _ => body,
+            };
+        }
+        Ok(http::Response::from_parts(parts, Body::from(body)))
+    }
+}
+
+impl<X> ChaosTransport<X> {
+    /// Replaces results in a JSON-RPC response body with `error`; other bodies are left untouched.
+    #[cfg(feature = "jsonrpc")]
+    fn replace_results(&self, body: Bytes, error: &JsonRpcError, rate: f64) -> Bytes {
+        let replace = |response: &mut serde_json::Value| {
+            if let Some(response) = response.as_object_mut()
+                && self.layer.chance(rate)
+            {
+                response.remove("result");
+                response.insert("error".into(), serde_json::json!({ "code": error.code, "message": error.message }));
+            }
+        };
+
+        let Ok(mut json) = serde_json::from_slice::<serde_json::Value>(&body) else {
+            return body;
+        };
+        match &mut json {
+            serde_json::Value::Array(responses) => responses.iter_mut().for_each(replace),
+            response => replace(response),
+        }
+        Bytes::from(json.to_string())
+    }
+}
+
+impl Fault {
+    fn alters_body(&self) -> bool {
+        match self {
+            Fault::TruncatedBody | Fault::MalformedJson => true,
+            #[cfg(feature = "jsonrpc")]
+            Fault::RpcError { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl<T: Target, X: Transport> Provider<T, X> {
+    /// Injects the faults of `layer` into requests sent by this provider's transport.
+    pub fn with_chaos(self, layer: ChaosLayer) -> Provider<T, ChaosTransport<X>> {
+        self.map_transport(|inner| ChaosTransport { inner, layer })
+    }
+}
+
+/// xorshift64* generator: small, fast and reproducible for a given seed.
+#[derive(Debug)]
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // The state must never be zero.
+        Self(if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed })
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// True with `probability`, always consuming one number so runs stay reproducible.
+    fn chance(&mut self, probability: f64) -> bool {
+        let sample = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        sample < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{provider::ProviderType, test_util::TestTarget, transport::InMemoryTransport};
+
+    fn users() -> TestTarget {
+        TestTarget::get("https://api.example", "/users")
+    }
+
+    fn orders() -> TestTarget {
+        TestTarget::get("https://api.example", "/orders")
+    }
+
+    fn backend() -> InMemoryTransport {
+        InMemoryTransport::new(|_| {
+            http::Response::new(Bytes::from_static(br#"[{"jsonrpc":"2.0","id":1,"result":"0x1"},{"jsonrpc":"2.0","id":2,"result":"0x2"}]"#))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_target_faults() {
+        let backend = backend();
+        let chaos = ChaosLayer::new(7)
+            .with_target_fault(&users(), 1.0, Fault::Status(503))
+            .with_target_fault(&orders(), 1.0, Fault::TruncatedBody);
+        let provider = Provider::<TestTarget>::default().with_transport(backend.clone()).with_chaos(chaos);
+
+        let response = provider.request(users()).await.unwrap();
+        assert_eq!(response.status(), 503);
+        assert!(backend.requests().is_empty());
+
+        let body = provider.request(orders()).await.unwrap().bytes().await.unwrap();
+        assert_eq!(body.len(), 40);
+        assert!(serde_json::from_slice::<serde_json::Value>(&body).is_err());
+        assert_eq!(backend.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_seeded_faults_are_reproducible() {
+        let run = |seed| async move {
+            let chaos = ChaosLayer::new(seed).with_fault(0.5, Fault::ConnectionError);
+            let provider = Provider::<TestTarget>::default().with_transport(backend()).with_chaos(chaos);
+            let mut outcomes = Vec::new();
+            for _ in 0..32 {
+                outcomes.push(provider.request(users()).await.is_ok());
+            }
+            outcomes
+        };
+
+        let outcomes = run(1234).await;
+        assert_eq!(outcomes, run(1234).await);
+        assert!(outcomes.contains(&true) && outcomes.contains(&false));
+        assert_ne!(outcomes, run(99).await);
+    }
+
+    #[tokio::test]
+    async fn test_timeout_waits_for_request_timeout() {
+        let chaos = ChaosLayer::new(1).with_fault(1.0, Fault::Timeout);
+        let provider = Provider::<TestTarget>::new(None, None, Some(Duration::from_millis(50)))
+            .with_transport(backend())
+            .with_chaos(chaos);
+
+        let started = std::time::Instant::now();
+        let err = provider.request(users()).await.unwrap_err();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        assert_eq!(err.to_string(), "Transport error: chaos: request timed out");
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_rpc_errors_in_batch_responses() {
+        let error = JsonRpcError {
+            code: -32005,
+            message: "limit exceeded".into(),
+        };
+        let chaos = ChaosLayer::new(3).with_fault(1.0, Fault::RpcError { error, rate: 1.0 });
+        let provider = Provider::<TestTarget>::default().with_transport(backend()).with_chaos(chaos);
+
+        let body: serde_json::Value = provider.request(users()).await.unwrap().json().await.unwrap();
+        assert_eq!(body[0]["id"], 1);
+        assert_eq!(body[0]["error"]["code"], -32005);
+        assert!(body[1].get("result").is_none());
+    }
+}
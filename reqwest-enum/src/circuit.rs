@@ -0,0 +1,354 @@
+//! Circuit breaking per endpoint or per target.
+use crate::{error::Error, target::Target};
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests flow normally while outcomes are recorded.
+    Closed,
+    /// Requests fail fast with `Error::CircuitOpen` until the cool-down elapses.
+    Open,
+    /// A limited number of trial requests decide whether to close or re-open the circuit.
+    HalfOpen,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CircuitBreakerConfig {
+    /// Failure rate (0.0 - 1.0) over the sliding window at which the circuit opens.
+    pub failure_rate_threshold: f64,
+    /// Number of most recent calls the failure rate is computed over.
+    pub window_size: usize,
+    /// Calls needed in the window before the failure rate is evaluated.
+    pub minimum_calls: usize,
+    /// How long the circuit stays open before allowing trial calls.
+    pub cooldown: Duration,
+    /// Trial calls allowed while half-open; all must succeed to close the circuit.
+    pub half_open_calls: usize,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_rate_threshold: 0.5,
+            window_size: 20,
+            minimum_calls: 10,
+            cooldown: Duration::from_secs(30),
+            half_open_calls: 1,
+        }
+    }
+}
+
+/// What a circuit is kept for.
+pub enum CircuitKey<T> {
+    /// One circuit per origin (scheme, host and port) of the request URL the provider built, so
+    /// `with_base_url` and `EndpointFn` overrides are taken into account.
+    Origin,
+    /// One circuit per key returned by the function, e.g. per `Target` variant.
+    Custom(fn(&T) -> String),
+}
+
+type StateChangeFn = Box<dyn Fn(&str, CircuitState, CircuitState) + Send + Sync>;
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    outcomes: VecDeque<bool>,
+    opened_at: Option<Instant>,
+    half_open_in_flight: usize,
+    half_open_successes: usize,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            outcomes: VecDeque::new(),
+            opened_at: None,
+            half_open_in_flight: 0,
+            half_open_successes: 0,
+        }
+    }
+
+    fn transition(&mut self, to: CircuitState) -> Option<(CircuitState, CircuitState)> {
+        let from = self.state;
+        if from == to {
+            return None;
+        }
+        self.state = to;
+        self.half_open_in_flight = 0;
+        self.half_open_successes = 0;
+        match to {
+            CircuitState::Open => self.opened_at = Some(Instant::now()),
+            CircuitState::Closed => self.outcomes.clear(),
+            CircuitState::HalfOpen => {}
+        }
+        Some((from, to))
+    }
+}
+
+/// Circuit breaker (closed / open / half-open) for a `Provider`, see `Provider::with_circuit_breaker`.
+///
+/// While a circuit is open, requests fail immediately with `Error::CircuitOpen` instead of waiting
+/// for a dead downstream to time out. A call fails if it errors or gets a 5xx response.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let breaker = CircuitBreaker::new(CircuitBreakerConfig::default())
+///     .on_state_change(|key, from, to| alert(format!("{key}: {from:?} -> {to:?}")));
+/// let provider = Provider::<HttpBin>::default().with_circuit_breaker(breaker);
+/// ```
+pub struct CircuitBreaker<T> {
+    config: CircuitBreakerConfig,
+    key: CircuitKey<T>,
+    circuits: Mutex<HashMap<String, Circuit>>,
+    on_state_change: Option<StateChangeFn>,
+}
+
+impl<T> std::fmt::Debug for CircuitBreaker<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CircuitBreaker")
+            .field("config", &self.config)
+            .field("circuits", &self.circuits)
+            .field("on_state_change", &self.on_state_change.as_ref().map(|_| "<function>"))
+            .finish()
+    }
+}
+
+impl<T: Target> CircuitBreaker<T> {
+    /// Creates a breaker with one circuit per request origin, see `CircuitKey::Origin`.
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            key: CircuitKey::Origin,
+            circuits: Mutex::new(HashMap::new()),
+            on_state_change: None,
+        }
+    }
+
+    /// Keeps one circuit per key returned by `key_fn` instead of per origin.
+    pub fn keyed_by(mut self, key_fn: fn(&T) -> String) -> Self {
+        self.key = CircuitKey::Custom(key_fn);
+        self
+    }
+
+    /// Called with the circuit key, previous and new state whenever a circuit changes state.
+    pub fn on_state_change<F>(mut self, callback: F) -> Self
+    where
+        F: Fn(&str, CircuitState, CircuitState) + Send + Sync + 'static,
+    {
+        self.on_state_change = Some(Box::new(callback));
+        self
+    }
+
+    /// Current state of the circuit for `key`; unknown keys are closed.
+    pub fn state(&self, key: &str) -> CircuitState {
+        self.circuits
+            .lock()
+            .unwrap()
+            .get(key)
+            .map_or(CircuitState::Closed, |circuit| circuit.state)
+    }
+
+    pub(crate) fn key_for(&self, target: &T, url: &reqwest::Url) -> String {
+        match &self.key {
+            CircuitKey::Origin => url.origin().ascii_serialization(),
+            CircuitKey::Custom(key_fn) => key_fn(target),
+        }
+    }
+
+    /// Admits a call for `key`, or fails fast if its circuit is open.
+    pub(crate) fn acquire(&self, key: String) -> Result<CircuitPermit<'_, T>, Error> {
+        let mut changed = None;
+        let admitted = {
+            let mut circuits = self.circuits.lock().unwrap();
+            let circuit = circuits.entry(key.clone()).or_insert_with(Circuit::new);
+            if circuit.state == CircuitState::Open
+                && circuit.opened_at.is_some_and(|at| at.elapsed() >= self.config.cooldown)
+            {
+                changed = circuit.transition(CircuitState::HalfOpen);
+            }
+            match circuit.state {
+                CircuitState::Closed => true,
+                CircuitState::Open => false,
+                CircuitState::HalfOpen if circuit.half_open_in_flight < self.config.half_open_calls.max(1) => {
+                    circuit.half_open_in_flight += 1;
+                    true
+                }
+                CircuitState::HalfOpen => false,
+            }
+        };
+        self.notify(&key, changed);
+
+        if admitted {
+            Ok(CircuitPermit {
+                breaker: self,
+                key,
+                recorded: false,
+            })
+        } else {
+            Err(Error::CircuitOpen { key })
+        }
+    }
+
+    fn record(&self, key: &str, success: bool) {
+        let changed = {
+            let mut circuits = self.circuits.lock().unwrap();
+            let circuit = circuits.entry(key.to_string()).or_insert_with(Circuit::new);
+            match circuit.state {
+                CircuitState::Closed => {
+                    circuit.outcomes.push_back(success);
+                    while circuit.outcomes.len() > self.config.window_size.max(1) {
+                        circuit.outcomes.pop_front();
+                    }
+                    let failures = circuit.outcomes.iter().filter(|ok| !**ok).count();
+                    let rate = failures as f64 / circuit.outcomes.len() as f64;
+                    if circuit.outcomes.len() >= self.config.minimum_calls && rate >= self.config.failure_rate_threshold {
+                        circuit.transition(CircuitState::Open)
+                    } else {
+                        None
+                    }
+                }
+                CircuitState::HalfOpen if success => {
+                    circuit.half_open_in_flight = circuit.half_open_in_flight.saturating_sub(1);
+                    circuit.half_open_successes += 1;
+                    if circuit.half_open_successes >= self.config.half_open_calls.max(1) {
+                        circuit.transition(CircuitState::Closed)
+                    } else {
+                        None
+                    }
+                }
+                CircuitState::HalfOpen => circuit.transition(CircuitState::Open),
+                // a call admitted before the circuit opened
+                CircuitState::Open => None,
+            }
+        };
+        self.notify(key, changed);
+    }
+
+    fn release(&self, key: &str) {
+        let mut circuits = self.circuits.lock().unwrap();
+        if let Some(circuit) = circuits.get_mut(key)
+            && circuit.state == CircuitState::HalfOpen
+        {
+            circuit.half_open_in_flight = circuit.half_open_in_flight.saturating_sub(1);
+        }
+    }
+
+    fn notify(&self, key: &str, changed: Option<(CircuitState, CircuitState)>) {
+        if let (Some(callback), Some((from, to))) = (&self.on_state_change, changed) {
+            #[cfg(feature = "tracing")]
+            tracing::info!(key, ?from, ?to, "circuit state changed");
+            callback(key, from, to);
+        }
+    }
+}
+
+/// An admitted call; dropping it without `record` (e.g. when the request future is cancelled)
+/// frees its half-open slot without counting an outcome.
+pub(crate) struct CircuitPermit<'a, T: Target> {
+    breaker: &'a CircuitBreaker<T>,
+    key: String,
+    recorded: bool,
+}
+
+impl<T: Target> CircuitPermit<'_, T> {
+    pub(crate) fn record(mut self, success: bool) {
+        self.recorded = true;
+        self.breaker.record(&self.key, success);
+    }
+}
+
+impl<T: Target> Drop for CircuitPermit<'_, T> {
+    fn drop(&mut self) {
+        if !self.recorded {
+            self.breaker.release(&self.key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        provider::{Provider, ProviderType},
+        test_util::{self, TestTarget},
+    };
+    use std::sync::{
+        Arc,
+        atomic::{AtomicU16, Ordering},
+    };
+
+    fn config() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failure_rate_threshold: 0.5,
+            window_size: 4,
+            minimum_calls: 2,
+            cooldown: Duration::from_millis(50),
+            half_open_calls: 1,
+        }
+    }
+
+    #[test]
+    fn test_state_transitions() {
+        let breaker = CircuitBreaker::<TestTarget>::new(config());
+        breaker.acquire("a".into()).unwrap().record(true);
+        breaker.acquire("a".into()).unwrap().record(false);
+        assert_eq!(breaker.state("a"), CircuitState::Open);
+        assert!(matches!(breaker.acquire("a".into()), Err(Error::CircuitOpen { .. })));
+        assert_eq!(breaker.state("b"), CircuitState::Closed);
+
+        std::thread::sleep(Duration::from_millis(60));
+        let trial = breaker.acquire("a".into()).unwrap();
+        assert_eq!(breaker.state("a"), CircuitState::HalfOpen);
+        // only one trial call at a time
+        assert!(breaker.acquire("a".into()).is_err());
+        trial.record(true);
+        assert_eq!(breaker.state("a"), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_key_is_the_request_origin() {
+        let breaker = CircuitBreaker::<TestTarget>::new(config());
+        // e.g. an `EndpointFn` routed the target away from its own base URL
+        let target = TestTarget::get("http://configured.example", "/status");
+        let url = reqwest::Url::parse("https://routed.example:8443/v1/status?x=1").unwrap();
+        assert_eq!(breaker.key_for(&target, &url), "https://routed.example:8443");
+    }
+
+    #[tokio::test]
+    async fn test_provider_fails_fast_while_open() {
+        let status = Arc::new(AtomicU16::new(500));
+        let server_status = status.clone();
+        let server = test_util::serve(move |_| (server_status.load(Ordering::SeqCst), "{}".into())).await;
+
+        let transitions = Arc::new(Mutex::new(Vec::new()));
+        let recorded = transitions.clone();
+        let breaker = CircuitBreaker::new(config()).on_state_change(move |_, from, to| {
+            recorded.lock().unwrap().push((from, to));
+        });
+        let provider = Provider::<TestTarget>::default().with_circuit_breaker(breaker);
+        let target = || TestTarget::get(server.url(), "/status");
+
+        provider.request(target()).await.unwrap();
+        provider.request(target()).await.unwrap();
+        assert!(matches!(provider.request(target()).await, Err(Error::CircuitOpen { key }) if key == server.url()));
+        assert_eq!(server.received_requests().len(), 2);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        status.store(200, Ordering::SeqCst);
+        provider.request(target()).await.unwrap();
+        assert_eq!(
+            *transitions.lock().unwrap(),
+            vec![
+                (CircuitState::Closed, CircuitState::Open),
+                (CircuitState::Open, CircuitState::HalfOpen),
+                (CircuitState::HalfOpen, CircuitState::Closed),
+            ]
+        );
+    }
+}
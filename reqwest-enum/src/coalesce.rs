@@ -0,0 +1,299 @@
+//! Automatic coalescing of concurrent JSON-RPC calls into batches, similar to DataLoader.
+use crate::{
+    jsonrpc::{JsonRpcError, JsonRpcResult},
+    provider::{Provider, match_batch_results},
+    target::JsonRpcTarget,
+    transport::{DefaultTransport, Transport},
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+type Waiter = oneshot::Sender<Result<JsonRpcResult<Value>, JsonRpcError>>;
+
+/// How long calls are queued and how many are sent together.
+#[derive(Debug, Clone, Copy)]
+pub struct CoalesceConfig {
+    /// How long the first queued call waits for others before the batch is flushed.
+    pub delay: Duration,
+    /// A batch is flushed immediately once it holds this many calls.
+    pub max_batch_size: usize,
+}
+
+impl Default for CoalesceConfig {
+    fn default() -> Self {
+        Self {
+            delay: Duration::from_millis(5),
+            max_batch_size: 100,
+        }
+    }
+}
+
+/// Calls are only batched together when their requests go to the same URL with the same
+/// method and headers, including authentication.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct BatchKey {
+    method: String,
+    url: String,
+    headers: Vec<(String, Vec<u8>)>,
+}
+
+impl BatchKey {
+    fn of<T: JsonRpcTarget, X: Transport>(provider: &Provider<T, X>, target: &T) -> Result<Self, crate::Error> {
+        let request = provider.request_builder(target)?.build()?;
+        let mut headers = request
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.as_str().to_string(), value.as_bytes().to_vec()))
+            .collect::<Vec<_>>();
+        headers.sort();
+        Ok(Self {
+            method: request.method().to_string(),
+            url: request.url().to_string(),
+            headers,
+        })
+    }
+}
+
+/// Calls waiting to be sent to one `BatchKey`.
+struct Batch<T> {
+    /// Unique across batches, so a delayed flush doesn't send a newer batch early.
+    id: u64,
+    targets: Vec<T>,
+    waiters: Vec<Waiter>,
+}
+
+struct Queues<T> {
+    next_id: u64,
+    batches: HashMap<BatchKey, Batch<T>>,
+}
+
+/// Wraps a `Provider` so that individual `call`s issued concurrently (e.g. from different tasks)
+/// are queued for a short window and sent as one JSON-RPC batch, each caller receiving its own result.
+///
+/// Calls are grouped by the request they would be sent as: only calls with the same URL, method
+/// and headers (including authentication) share a batch, so targets with per-call endpoints such
+/// as `DynamicRpc` are never sent to another call's endpoint. Notifications are rejected, use
+/// `Provider::notify` or `Provider::batch` for them.
+///
+/// Flushes are spawned on the current tokio runtime.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let provider = CoalescingProvider::new(Provider::<EthereumRPC>::default(), CoalesceConfig::default());
+/// let (balance, code) = tokio::join!(
+///     provider.call::<String>(EthereumRPC::GetBalance(address)),
+///     provider.call::<String>(EthereumRPC::GetCode(address, BlockParameter::Latest)),
+/// );
+/// ```
+pub struct CoalescingProvider<T: JsonRpcTarget, X: Transport = DefaultTransport> {
+    inner: Arc<Provider<T, X>>,
+    config: CoalesceConfig,
+    queues: Arc<Mutex<Queues<T>>>,
+}
+
+impl<T: JsonRpcTarget, X: Transport> std::fmt::Debug for CoalescingProvider<T, X> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CoalescingProvider")
+            .field("inner", &self.inner)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+impl<T, X> CoalescingProvider<T, X>
+where
+    T: JsonRpcTarget + Send + Sync + 'static,
+    X: Transport,
+{
+    pub fn new(provider: Provider<T, X>, config: CoalesceConfig) -> Self {
+        Self {
+            inner: Arc::new(provider),
+            config,
+            queues: Arc::new(Mutex::new(Queues {
+                next_id: 0,
+                batches: HashMap::new(),
+            })),
+        }
+    }
+
+    /// The wrapped provider, for requests that should bypass coalescing.
+    pub fn inner(&self) -> &Provider<T, X> {
+        &self.inner
+    }
+
+    /// Queues `target` into the next batch and waits for its result.
+    pub async fn call<U: DeserializeOwned>(&self, target: T) -> Result<JsonRpcResult<U>, JsonRpcError> {
+        if target.is_notification() {
+            return Err(JsonRpcError::invalid_request("Notifications have no response, use Provider::notify"));
+        }
+        let key = BatchKey::of(&self.inner, &target)?;
+
+        let (sender, receiver) = oneshot::channel();
+        {
+            let mut queues = self.queues.lock().unwrap();
+            let Queues { next_id, batches } = &mut *queues;
+            let batch = batches.entry(key.clone()).or_insert_with(|| {
+                *next_id += 1;
+                Batch {
+                    id: *next_id,
+                    targets: Vec::new(),
+                    waiters: Vec::new(),
+                }
+            });
+            batch.targets.push(target);
+            batch.waiters.push(sender);
+
+            if batch.targets.len() >= self.config.max_batch_size.max(1) {
+                let batch = batches.remove(&key).expect("batch was just queued");
+                tokio::spawn(flush(self.inner.clone(), batch));
+            } else if batch.targets.len() == 1 {
+                let (inner, shared, id) = (self.inner.clone(), self.queues.clone(), batch.id);
+                let delay = self.config.delay;
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    let batch = {
+                        let mut queues = shared.lock().unwrap();
+                        match queues.batches.get(&key) {
+                            Some(batch) if batch.id == id => queues.batches.remove(&key),
+                            _ => None,
+                        }
+                    };
+                    if let Some(batch) = batch {
+                        flush(inner, batch).await;
+                    }
+                });
+            }
+        }
+
+        let result = receiver.await.map_err(|_| JsonRpcError::internal_error("Coalesced batch was dropped before completing"))??;
+        result.deserialize().map_err(|e| JsonRpcError::parse_error(format!("Failed to parse JSON response: {}", e)))
+    }
+}
+
+/// Sends one batch and hands every waiter the result matching its request.
+/// All targets share a `BatchKey`, so the first one stands for the URL, headers and auth.
+async fn flush<T: JsonRpcTarget, X: Transport>(provider: Arc<Provider<T, X>>, batch: Batch<T>) {
+    let Batch { targets, waiters, .. } = batch;
+    let requests = provider.rpc_requests(&targets);
+    match provider.send_rpc_batch::<Value>(&targets[0], &requests).await {
+        Ok(results) => {
+            // notifications are rejected before they are queued, so every request has an answer
+            for (result, waiter) in match_batch_results(&requests, results).into_iter().zip(waiters) {
+                let _ = waiter.send(Ok(result));
+            }
+        }
+        Err(err) => {
+            for waiter in waiters {
+                let _ = waiter.send(Err(err.clone()));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{self, JsonBody, TestTarget},
+        testing::MockServer,
+    };
+
+    fn echo(url: &str, value: u64) -> TestTarget {
+        TestTarget::rpc(url, "echo", vec![Value::from(value)])
+    }
+
+    /// Answers each call of a batch with its first param.
+    async fn echo_server() -> MockServer {
+        test_util::serve(|request| {
+            let answers = request
+                .json()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|req| serde_json::json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]}))
+                .collect::<Vec<_>>();
+            (200, Value::Array(answers).to_string())
+        })
+        .await
+    }
+
+    async fn call_all(provider: &CoalescingProvider<TestTarget>, url: &str, count: u64) -> Vec<u64> {
+        let calls = (0..count).map(|value| provider.call::<u64>(echo(url, value)));
+        futures::future::join_all(calls)
+            .await
+            .into_iter()
+            .map(|result| match result.unwrap() {
+                JsonRpcResult::Value(response) => response.result,
+                JsonRpcResult::Error(error) => panic!("unexpected error: {}", error.error),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_calls_share_one_batch() {
+        let server = echo_server().await;
+        let provider = CoalescingProvider::new(Provider::default(), CoalesceConfig::default());
+
+        assert_eq!(call_all(&provider, &server.url(), 5).await, vec![0, 1, 2, 3, 4]);
+        assert_eq!(server.received_requests().len(), 1);
+        assert_eq!(server.received_requests()[0].json().as_array().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_max_batch_size_flushes_early() {
+        let server = echo_server().await;
+        let config = CoalesceConfig {
+            delay: Duration::from_secs(60),
+            max_batch_size: 2,
+        };
+        let provider = CoalescingProvider::new(Provider::default(), config);
+
+        assert_eq!(call_all(&provider, &server.url(), 4).await, vec![0, 1, 2, 3]);
+        assert_eq!(server.received_requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_calls_to_different_endpoints_are_batched_separately() {
+        let (first, second) = (echo_server().await, echo_server().await);
+        let (first_url, second_url) = (first.url(), second.url());
+        let provider = CoalescingProvider::new(Provider::default(), CoalesceConfig::default());
+
+        let (first_values, second_values) = tokio::join!(
+            call_all(&provider, &first_url, 3),
+            call_all(&provider, &second_url, 2)
+        );
+        assert_eq!((first_values, second_values), (vec![0, 1, 2], vec![0, 1]));
+        assert_eq!(first.received_requests().len(), 1);
+        assert_eq!(first.received_requests()[0].json().as_array().unwrap().len(), 3);
+        assert_eq!(second.received_requests()[0].json().as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_notifications_are_rejected() {
+        let server = echo_server().await;
+        let provider = CoalescingProvider::new(Provider::default(), CoalesceConfig::default());
+
+        let notification = echo(&server.url(), 0).notification();
+        let err = provider.call::<u64>(notification).await.unwrap_err();
+        assert_eq!(err.code, -32600);
+        assert!(server.received_requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_repeating_ids() {
+        use crate::jsonrpc::JsonRpcId;
+
+        let server = echo_server().await;
+        let provider = Provider::default().with_id_generator(|| JsonRpcId::from("fixed"));
+        let provider = CoalescingProvider::new(provider, CoalesceConfig::default());
+
+        assert_eq!(call_all(&provider, &server.url(), 3).await, vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,355 @@
+//! In-flight request deduplication ("singleflight").
+use crate::{
+    error::Error,
+    provider::Provider,
+    target::Target,
+    transport::{DefaultTransport, Transport},
+};
+use bytes::Bytes;
+use futures::future::{BoxFuture, FutureExt, Shared};
+use reqwest::{StatusCode, header::HeaderMap};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+};
+
+#[cfg(feature = "jsonrpc")]
+use crate::{
+    jsonrpc::{JsonRpcError, JsonRpcResult},
+    provider::match_batch_results,
+    target::JsonRpcTarget,
+};
+
+/// Canonical identity of a built request: method, URL with the query as sent, sorted headers and
+/// body. Two requests with the same fingerprint would be identical on the wire (modulo header
+/// order); query parameter order is kept, since it can matter, e.g. for signed queries.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RequestFingerprint(Vec<u8>);
+
+impl RequestFingerprint {
+    /// Returns `None` for requests with a streaming body, which can't be compared.
+    pub fn of(request: &reqwest::Request) -> Option<Self> {
+        let body = match request.body() {
+            Some(body) => body.as_bytes()?,
+            None => &[],
+        };
+
+        let mut headers = request
+            .headers()
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_bytes()))
+            .collect::<Vec<_>>();
+        headers.sort();
+
+        let mut key = Vec::new();
+        key.extend_from_slice(request.method().as_str().as_bytes());
+        key.push(b'\n');
+        key.extend_from_slice(request.url().as_str().as_bytes());
+        for (name, value) in headers {
+            key.push(b'\n');
+            key.extend_from_slice(name.as_bytes());
+            key.push(b':');
+            key.extend_from_slice(value);
+        }
+        key.push(b'\n');
+        key.extend_from_slice(body);
+        Some(Self(key))
+    }
+}
+
+/// A fully read response, cheap to clone and hand to every deduplicated caller.
+#[derive(Debug, Clone)]
+pub struct SharedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl SharedResponse {
+    async fn read(response: reqwest::Response) -> Result<Self, Error> {
+        Ok(Self {
+            status: response.status(),
+            headers: response.headers().clone(),
+            body: response.bytes().await?,
+        })
+    }
+
+    /// Deserializes the body as JSON.
+    pub fn json<U: DeserializeOwned>(&self) -> Result<U, Error> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+type InFlight = Shared<BoxFuture<'static, Result<SharedResponse, Arc<Error>>>>;
+
+/// Opt-in deduplication layer over a `Provider`: concurrent requests with the same
+/// `RequestFingerprint` share one network call, and every awaiter gets a clone of the response.
+/// Once the shared call completes, the next identical request goes to the network again.
+///
+/// Shared calls go through the wrapped provider's circuit breaker and hedging policy, if set.
+/// A failure still shared with other callers is returned as `Error::Shared`; `Error::inner`
+/// gives the underlying error either way.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let provider = DedupProvider::new(Provider::<HttpBin>::default());
+/// // only one GET is sent
+/// let (a, b) = tokio::join!(provider.request(HttpBin::Get), provider.request(HttpBin::Get));
+/// ```
+pub struct DedupProvider<T: Target, X: Transport = DefaultTransport> {
+    inner: Arc<Provider<T, X>>,
+    in_flight: Mutex<HashMap<RequestFingerprint, (u64, InFlight)>>,
+    next_call: AtomicU64,
+}
+
+impl<T: Target, X: Transport> std::fmt::Debug for DedupProvider<T, X> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("DedupProvider")
+            .field("inner", &self.inner)
+            .field("in_flight", &self.in_flight.lock().map(|m| m.len()).unwrap_or_default())
+            .finish()
+    }
+}
+
+impl<T: Target, X: Transport> DedupProvider<T, X> {
+    pub fn new(provider: Provider<T, X>) -> Self {
+        Self {
+            inner: Arc::new(provider),
+            in_flight: Mutex::new(HashMap::new()),
+            next_call: AtomicU64::new(0),
+        }
+    }
+
+    /// The wrapped provider, for requests that should bypass deduplication.
+    pub fn inner(&self) -> &Provider<T, X> {
+        &self.inner
+    }
+}
+
+impl<T: Target + Send + Sync + 'static, X: Transport> DedupProvider<T, X> {
+    /// Sends `target`, joining an identical in-flight request if there is one.
+    pub async fn request(&self, target: T) -> Result<SharedResponse, Error> {
+        let request = self.inner.request_builder(&target)?.build()?;
+        let Some(key) = RequestFingerprint::of(&request) else {
+            let response = self.inner.send(&target, request).await?;
+            return SharedResponse::read(response).await;
+        };
+
+        let (call, future) = {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            match in_flight.get(&key) {
+                Some((call, future)) => (*call, future.clone()),
+                None => {
+                    let call = self.next_call.fetch_add(1, Ordering::Relaxed);
+                    let inner = self.inner.clone();
+                    let future = async move {
+                        let response = inner.send(&target, request).await.map_err(Arc::new)?;
+                        SharedResponse::read(response).await.map_err(Arc::new)
+                    }
+                    .boxed()
+                    .shared();
+                    in_flight.insert(key.clone(), (call, future.clone()));
+                    (call, future)
+                }
+            }
+        };
+
+        let result = future.await;
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            if in_flight.get(&key).is_some_and(|(current, _)| *current == call) {
+                in_flight.remove(&key);
+            }
+        }
+        // once no other caller holds the error it is returned as is, see `Error::inner`
+        result.map_err(|error| Arc::try_unwrap(error).unwrap_or_else(Error::Shared))
+    }
+
+    /// Like `request`, checking the status and deserializing the shared body as JSON.
+    pub async fn request_json<U: DeserializeOwned>(&self, target: T) -> Result<U, Error> {
+        let response = self.request(target).await?;
+        if response.status.is_client_error() || response.status.is_server_error() {
+            // reuse reqwest's status error
+            let mut status = http::Response::new(response.body.clone());
+            *status.status_mut() = response.status;
+            reqwest::Response::from(status).error_for_status()?;
+        }
+        response.json()
+    }
+}
+
+#[cfg(feature = "jsonrpc")]
+impl<T: JsonRpcTarget, X: Transport> DedupProvider<T, X> {
+    /// JSON-RPC batch where identical method + params pairs are sent once and the result is
+    /// fanned out to every duplicate (sharing the id of the request actually sent).
+    /// Notifications are never merged. Results are returned in input order.
+    pub async fn batch<U: DeserializeOwned>(&self, targets: Vec<T>) -> Result<Vec<JsonRpcResult<U>>, JsonRpcError> {
+        if targets.is_empty() {
+            return Err(JsonRpcError::invalid_request("Invalid Request"));
+        }
+
+        // input index -> index into `unique`, for calls expecting a result
+        let mut slots = Vec::new();
+        let mut seen = HashMap::<(String, String), usize>::new();
+        let mut unique = Vec::<T>::new();
+        for target in targets {
+            if target.is_notification() {
+                unique.push(target);
+                continue;
+            }
+            let key = (target.method_name().into_owned(), serde_json::Value::Array(target.params()).to_string());
+            let idx = *seen.entry(key).or_insert_with(|| {
+                unique.push(target);
+                unique.len() - 1
+            });
+            slots.push(idx);
+        }
+
+        let requests = self.inner.rpc_requests(&unique);
+        let results = self.inner.send_rpc_batch::<serde_json::Value>(&unique[0], &requests).await?;
+        // one answer per unique request, `None` for notifications
+        let mut matched = match_batch_results(&requests, results).into_iter();
+        let answers = requests
+            .iter()
+            .map(|request| if request.is_notification() { None } else { matched.next() })
+            .collect::<Vec<_>>();
+
+        slots
+            .into_iter()
+            .map(|idx| {
+                let result = answers[idx].clone().expect("non-notification request has an answer");
+                result.deserialize().map_err(|e| JsonRpcError::parse_error(format!("Failed to parse JSON response: {}", e)))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{self, JsonBody, TestTarget};
+    use std::time::Duration;
+
+    fn item(url: String, id: u64) -> TestTarget {
+        TestTarget::post(url, "/items")
+            .with_json(serde_json::json!({ "id": id }))
+            .with_rpc("item", vec![serde_json::Value::from(id)])
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_concurrent_identical_requests_share_one_call() {
+        let server = test_util::serve(|request| {
+            std::thread::sleep(Duration::from_millis(50));
+            (200, request.json().to_string())
+        })
+        .await;
+        let provider = DedupProvider::new(Provider::default());
+
+        let (a, b, c) = tokio::join!(
+            provider.request_json::<serde_json::Value>(item(server.url(), 1)),
+            provider.request_json::<serde_json::Value>(item(server.url(), 1)),
+            provider.request_json::<serde_json::Value>(item(server.url(), 2)),
+        );
+        assert_eq!(a.unwrap()["id"], 1);
+        assert_eq!(b.unwrap()["id"], 1);
+        assert_eq!(c.unwrap()["id"], 2);
+        assert_eq!(server.received_requests().len(), 2);
+
+        // nothing in flight any more, so this one hits the network again
+        provider.request(item(server.url(), 1)).await.unwrap();
+        assert_eq!(server.received_requests().len(), 3);
+    }
+
+    #[test]
+    fn test_fingerprint_keeps_query_order() {
+        let client = reqwest::Client::new();
+        let fingerprint = |url| RequestFingerprint::of(&client.get(url).build().unwrap()).unwrap();
+        assert_eq!(fingerprint("http://a.example/x?b=1&a=2"), fingerprint("http://a.example/x?b=1&a=2"));
+        assert_ne!(fingerprint("http://a.example/x?b=1&a=2"), fingerprint("http://a.example/x?a=2&b=1"));
+    }
+
+    #[cfg(feature = "circuit")]
+    #[tokio::test]
+    async fn test_requests_go_through_circuit_breaker() {
+        use crate::circuit::{CircuitBreaker, CircuitBreakerConfig};
+
+        let server = test_util::serve(|_| (500, "{}".into())).await;
+        let breaker = CircuitBreaker::new(CircuitBreakerConfig {
+            minimum_calls: 1,
+            ..Default::default()
+        });
+        let provider = DedupProvider::new(Provider::default().with_circuit_breaker(breaker));
+
+        assert_eq!(provider.request(item(server.url(), 1)).await.unwrap().status, 500);
+        let err = provider.request(item(server.url(), 1)).await.unwrap_err();
+        assert!(matches!(err, Error::CircuitOpen { .. }), "{}", err);
+
+        let shared = Error::Shared(Arc::new(err));
+        assert!(matches!(shared.inner(), Error::CircuitOpen { .. }));
+        assert_eq!(server.received_requests().len(), 1);
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_batch_sends_duplicates_once() {
+        let server = test_util::serve(|request| {
+            let answers = request
+                .json()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|req| serde_json::json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]}))
+                .collect::<Vec<_>>();
+            (200, serde_json::Value::Array(answers).to_string())
+        })
+        .await;
+        let provider = DedupProvider::new(Provider::default());
+        let targets = [1, 2, 1, 1].map(|id| item(server.url(), id)).into_iter().collect();
+
+        let results = provider.batch::<u64>(targets).await.unwrap();
+        let values = results
+            .into_iter()
+            .map(|result| match result {
+                JsonRpcResult::Value(response) => response.result,
+                JsonRpcResult::Error(error) => panic!("unexpected error: {}", error.error),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![1, 2, 1, 1]);
+        assert_eq!(server.received_requests()[0].json().as_array().unwrap().len(), 2);
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_batch_with_repeating_ids() {
+        use crate::jsonrpc::JsonRpcId;
+
+        let server = test_util::serve(|request| {
+            let answers = request
+                .json()
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|req| serde_json::json!({"jsonrpc": "2.0", "id": req["id"], "result": req["params"][0]}))
+                .collect::<Vec<_>>();
+            (200, serde_json::Value::Array(answers).to_string())
+        })
+        .await;
+        let provider = DedupProvider::new(Provider::default().with_id_generator(|| JsonRpcId::from("fixed")));
+        let targets = [1, 2, 1].map(|id| item(server.url(), id)).into_iter().collect();
+
+        let results = provider.batch::<u64>(targets).await.unwrap();
+        let values = results
+            .into_iter()
+            .map(|result| match result {
+                JsonRpcResult::Value(response) => response.result,
+                JsonRpcResult::Error(error) => panic!("unexpected error: {}", error.error),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![1, 2, 1]);
+    }
+}
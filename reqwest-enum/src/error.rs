@@ -1,7 +1,11 @@
 // src/error.rs
+use std::sync::Arc;
 use thiserror::Error;
 
+/// Errors returned by providers. Variants may be added (e.g. by enabling the `middleware`
+/// feature), so matches need a wildcard arm.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum Error {
     #[error("Reqwest HTTP error: {0}")]
     Reqwest(#[from] reqwest::Error),
@@ -12,4 +16,39 @@ pub enum Error {
 
     #[error("JSON serialization/deserialization error: {0}")]
     SerdeJson(#[from] serde_json::Error),
+
+    /// Query parameters could not be serialized, see `QueryParams::from_serialize`.
+    #[error("Query serialization error: {0}")]
+    Query(#[from] serde_urlencoded::ser::Error),
+
+    /// A request URL could not be built from the base URL, path and query.
+    #[error("Invalid URL {url}: {reason}")]
+    InvalidUrl { url: String, reason: String },
+
+    /// The circuit breaker for `key` is open, the request was not sent.
+    #[error("Circuit open for {key}")]
+    CircuitOpen { key: String },
+
+    /// A multi-endpoint provider was created without any endpoints.
+    #[error("No endpoints configured")]
+    NoEndpoints,
+
+    /// An error raised by a custom `Transport`.
+    #[error("Transport error: {0}")]
+    Transport(Box<dyn std::error::Error + Send + Sync>),
+
+    /// An error from a request whose result was shared by several deduplicated callers; match
+    /// on `Error::inner` to see through it.
+    #[error("{0}")]
+    Shared(Arc<Error>),
+}
+
+impl Error {
+    /// The underlying error, looking through `Error::Shared`.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Shared(error) => error.inner(),
+            error => error,
+        }
+    }
 }
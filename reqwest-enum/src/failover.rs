@@ -0,0 +1,430 @@
+//! Multi-endpoint failover and load balancing.
+use crate::{
+    error::Error,
+    http::HTTPResponse,
+    provider::Provider,
+    target::{Target, is_safe_method},
+    transport::{DefaultTransport, Transport},
+};
+use serde::de::DeserializeOwned;
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "jsonrpc")]
+use crate::{
+    jsonrpc::{JsonRpcError, JsonRpcResult},
+    target::JsonRpcTarget,
+};
+
+/// One of several equivalent base URLs serving the same API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub url: String,
+    /// Relative share of traffic under `RoutingStrategy::RoundRobin`.
+    pub weight: u32,
+}
+
+impl Endpoint {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self::weighted(url, 1)
+    }
+
+    pub fn weighted(url: impl Into<String>, weight: u32) -> Self {
+        Self {
+            url: url.into(),
+            weight: weight.max(1),
+        }
+    }
+}
+
+/// How the first endpoint for a request is chosen; the remaining healthy endpoints are
+/// tried in list order if it fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoutingStrategy {
+    /// Smooth weighted round-robin over healthy endpoints.
+    #[default]
+    RoundRobin,
+    /// The healthy endpoint with the lowest average latency of successful requests (untried
+    /// endpoints first, endpoints whose last request failed last).
+    LeastLatency,
+    /// Always the first healthy endpoint in list order.
+    Priority,
+}
+
+/// When an endpoint is taken out of rotation and when it gets another chance.
+#[derive(Debug, Clone, Copy)]
+pub struct HealthPolicy {
+    /// Consecutive failures after which an endpoint is marked unhealthy.
+    pub max_consecutive_failures: u32,
+    /// How long an unhealthy endpoint is skipped before it is tried again.
+    pub cooldown: Duration,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            max_consecutive_failures: 3,
+            cooldown: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct EndpointState {
+    consecutive_failures: u32,
+    unhealthy_since: Option<Instant>,
+    /// Exponentially weighted moving average of request latency.
+    latency: Option<Duration>,
+    /// Running weight for smooth weighted round-robin.
+    current_weight: i64,
+}
+
+/// Routes requests for `T` across a pool of equivalent endpoints, marks endpoints unhealthy after
+/// consecutive failures and transparently fails over to the next one, for both REST requests and
+/// JSON-RPC calls and batches.
+///
+/// A request fails over on transport errors and on HTTP 5xx / 429 responses; JSON-RPC error
+/// objects are application errors and are returned as-is. Only idempotent targets (GET, HEAD and
+/// OPTIONS by default, see `with_idempotent_fn`) are resent after a failure; other requests only
+/// fail over when they were never sent (open circuit or connection error), and JSON-RPC calls and
+/// batches of non-idempotent targets go to a single endpoint. Since JSON-RPC is sent with POST,
+/// read-only calls need `with_idempotent_fn` to fail over.
+///
+/// Every endpoint gets its own `Provider` pointed at it with `Provider::with_base_url`, and
+/// requests go through that provider's circuit breaker and hedging policy, if set.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let provider = FailoverProvider::<EthereumRPC>::new(
+///     vec![Endpoint::weighted("https://node-a.example", 3), Endpoint::new("https://node-b.example")],
+///     RoutingStrategy::RoundRobin,
+/// )
+/// .with_idempotent_fn(|rpc| !matches!(rpc, EthereumRPC::SendRawTransaction(_)));
+/// let result: JsonRpcResult<String> = provider.call(EthereumRPC::ChainId).await?;
+/// ```
+pub struct FailoverProvider<T: Target, X: Transport = DefaultTransport> {
+    endpoints: Vec<Endpoint>,
+    providers: Vec<Provider<T, X>>,
+    states: Mutex<Vec<EndpointState>>,
+    strategy: RoutingStrategy,
+    health: HealthPolicy,
+    is_idempotent: fn(&T) -> bool,
+}
+
+impl<T: Target, X: Transport> std::fmt::Debug for FailoverProvider<T, X> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FailoverProvider")
+            .field("endpoints", &self.endpoints)
+            .field("strategy", &self.strategy)
+            .field("health", &self.health)
+            .finish()
+    }
+}
+
+impl<T: Target> FailoverProvider<T> {
+    /// Creates a pool with a default `Provider` per endpoint.
+    pub fn new(endpoints: Vec<Endpoint>, strategy: RoutingStrategy) -> Self {
+        let members = endpoints
+            .into_iter()
+            .map(|endpoint| (endpoint, Provider::default()))
+            .collect();
+        Self::with_providers(members, strategy)
+    }
+}
+
+impl<T: Target, X: Transport> FailoverProvider<T, X> {
+    /// Creates a pool from pre-configured providers (custom clients, timeouts, ...),
+    /// each of which is pointed at its endpoint's URL.
+    ///
+    /// **A provider created with an `EndpointFn` keeps sending to the URL that function returns**,
+    /// since it takes precedence over `with_base_url`: such a member ignores its endpoint's URL,
+    /// so only pass providers without one.
+    pub fn with_providers(members: Vec<(Endpoint, Provider<T, X>)>, strategy: RoutingStrategy) -> Self {
+        let (endpoints, providers): (Vec<_>, Vec<_>) = members
+            .into_iter()
+            .map(|(endpoint, provider)| {
+                let provider = provider.with_base_url(endpoint.url.clone());
+                (endpoint, provider)
+            })
+            .unzip();
+        let states = endpoints.iter().map(|_| EndpointState::default()).collect();
+        Self {
+            endpoints,
+            providers,
+            states: Mutex::new(states),
+            strategy,
+            health: HealthPolicy::default(),
+            is_idempotent: is_safe_method,
+        }
+    }
+
+    pub fn with_health_policy(mut self, health: HealthPolicy) -> Self {
+        self.health = health;
+        self
+    }
+
+    /// Decides which targets are safe to resend to another endpoint after a failure,
+    /// e.g. read-only JSON-RPC methods sent with POST.
+    pub fn with_idempotent_fn(mut self, is_idempotent: fn(&T) -> bool) -> Self {
+        self.is_idempotent = is_idempotent;
+        self
+    }
+
+    /// URLs of the endpoints currently in rotation.
+    pub fn healthy_endpoints(&self) -> Vec<&str> {
+        let states = self.states.lock().unwrap();
+        self.endpoints
+            .iter()
+            .zip(states.iter())
+            .filter(|(_, state)| self.is_available(state))
+            .map(|(endpoint, _)| endpoint.url.as_str())
+            .collect()
+    }
+
+    fn is_available(&self, state: &EndpointState) -> bool {
+        state
+            .unhealthy_since
+            .is_none_or(|since| since.elapsed() >= self.health.cooldown)
+    }
+
+    /// Order in which endpoints are tried for one request: the strategy's pick, the other
+    /// healthy endpoints, then unhealthy ones as a last resort.
+    fn attempt_order(&self) -> Vec<usize> {
+        let mut states = self.states.lock().unwrap();
+        let (mut healthy, unhealthy): (Vec<usize>, Vec<usize>) =
+            (0..states.len()).partition(|&idx| self.is_available(&states[idx]));
+
+        match self.strategy {
+            RoutingStrategy::Priority => {}
+            RoutingStrategy::LeastLatency => {
+                healthy.sort_by_key(|&idx| (states[idx].consecutive_failures > 0, states[idx].latency.unwrap_or_default()));
+            }
+            RoutingStrategy::RoundRobin => {
+                let total = healthy.iter().map(|&idx| i64::from(self.endpoints[idx].weight)).sum::<i64>();
+                for &idx in &healthy {
+                    states[idx].current_weight += i64::from(self.endpoints[idx].weight);
+                }
+                if let Some(pos) = (0..healthy.len()).max_by_key(|&pos| (states[healthy[pos]].current_weight, -(pos as i64))) {
+                    states[healthy[pos]].current_weight -= total;
+                    let picked = healthy.remove(pos);
+                    healthy.insert(0, picked);
+                }
+            }
+        }
+        healthy.extend(unhealthy);
+        healthy
+    }
+
+    fn record(&self, idx: usize, success: bool, latency: Duration) {
+        let mut states = self.states.lock().unwrap();
+        let state = &mut states[idx];
+        if success {
+            // fast failures must not make an endpoint look fast under `LeastLatency`
+            state.latency = Some(match state.latency {
+                Some(average) => average.mul_f64(0.8) + latency.mul_f64(0.2),
+                None => latency,
+            });
+            state.consecutive_failures = 0;
+            state.unhealthy_since = None;
+        } else {
+            state.consecutive_failures += 1;
+            if state.consecutive_failures >= self.health.max_consecutive_failures {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(endpoint = %self.endpoints[idx].url, "marking endpoint unhealthy");
+                state.unhealthy_since = Some(Instant::now());
+            }
+        }
+    }
+
+    /// Sends `target` to the first endpoint that doesn't fail, returning the last failure otherwise.
+    /// Non-idempotent targets only move on to the next endpoint if the request was never sent.
+    pub async fn request(&self, target: T) -> Result<HTTPResponse, Error> {
+        let idempotent = (self.is_idempotent)(&target);
+        let order = self.attempt_order();
+        let mut last = Err(Error::NoEndpoints);
+        for (attempt, &idx) in order.iter().enumerate() {
+            let provider = &self.providers[idx];
+            let started = Instant::now();
+            let request = provider.request_builder(&target)?.build()?;
+            let result = provider.send(&target, request).await;
+            let success = result
+                .as_ref()
+                .is_ok_and(|response| !is_retryable_status(response.status()));
+            self.record(idx, success, started.elapsed());
+            let may_resend = idempotent || result.as_ref().is_err_and(was_not_sent);
+            if success || !may_resend || attempt + 1 == order.len() {
+                return result;
+            }
+            last = result;
+        }
+        last
+    }
+
+    /// Like `request`, checking the status and deserializing the response body as JSON.
+    pub async fn request_json<U: DeserializeOwned>(&self, target: T) -> Result<U, Error> {
+        let response = self.request(target).await?.error_for_status()?;
+        Ok(response.json().await?)
+    }
+}
+
+#[cfg(feature = "jsonrpc")]
+impl<T: JsonRpcTarget, X: Transport> FailoverProvider<T, X> {
+    /// Endpoints to try for `targets`: the whole attempt order if they may be resent, only the
+    /// first pick otherwise.
+    fn attempts_for(&self, targets: &[T]) -> Vec<usize> {
+        let mut order = self.attempt_order();
+        if !targets.iter().all(self.is_idempotent) {
+            order.truncate(1);
+        }
+        order
+    }
+
+    /// Sends a JSON-RPC call, failing over on transport or parse errors.
+    pub async fn call<U: DeserializeOwned>(&self, target: T) -> Result<JsonRpcResult<U>, JsonRpcError> {
+        let mut last = Err(Error::NoEndpoints.into());
+        for idx in self.attempts_for(std::slice::from_ref(&target)) {
+            let started = Instant::now();
+            let result = self.providers[idx].call_ref(&target).await;
+            self.record(idx, result.is_ok(), started.elapsed());
+            if result.is_ok() {
+                return result;
+            }
+            last = result;
+        }
+        last
+    }
+
+    /// Sends a JSON-RPC batch, resending the whole batch to the next endpoint if one fails and
+    /// every target is idempotent.
+    pub async fn batch<U: DeserializeOwned>(&self, targets: Vec<T>) -> Result<Vec<JsonRpcResult<U>>, JsonRpcError> {
+        if targets.is_empty() {
+            return Err(JsonRpcError::invalid_request("Invalid Request"));
+        }
+
+        let mut last = Err(Error::NoEndpoints.into());
+        for idx in self.attempts_for(&targets) {
+            let provider = &self.providers[idx];
+            let requests = provider.rpc_requests(&targets);
+            let started = Instant::now();
+            let result = provider.send_rpc_batch(&targets[0], &requests).await;
+            self.record(idx, result.is_ok(), started.elapsed());
+            if result.is_ok() {
+                return result;
+            }
+            last = result;
+        }
+        last
+    }
+}
+
+fn is_retryable_status(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+}
+
+/// Whether the request certainly never reached the endpoint, so even a non-idempotent one can
+/// be sent elsewhere.
+fn was_not_sent(error: &Error) -> bool {
+    match error {
+        Error::CircuitOpen { .. } => true,
+        Error::Reqwest(e) => e.is_connect(),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::HTTPMethod,
+        test_util::{self, TestTarget},
+        testing::MockServer,
+    };
+
+    fn ping(method: HTTPMethod) -> TestTarget {
+        TestTarget::new(method, "http://replaced-by-the-pool", "/ping")
+    }
+
+    async fn server(status: u16, name: &'static str) -> MockServer {
+        test_util::serve(move |_| (status, format!("\"{}\"", name))).await
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_and_marks_unhealthy() {
+        let (down, up) = (server(503, "down").await, server(200, "up").await);
+        let provider = FailoverProvider::<TestTarget>::new(
+            vec![Endpoint::new(down.url()), Endpoint::new(up.url())],
+            RoutingStrategy::Priority,
+        )
+        .with_health_policy(HealthPolicy {
+            max_consecutive_failures: 2,
+            cooldown: Duration::from_secs(60),
+        });
+
+        for _ in 0..3 {
+            assert_eq!(provider.request_json::<String>(ping(HTTPMethod::GET)).await.unwrap(), "up");
+        }
+        // the failing endpoint was skipped once it became unhealthy
+        assert_eq!(down.received_requests().len(), 2);
+        assert_eq!(provider.healthy_endpoints(), vec![up.url()]);
+    }
+
+    #[tokio::test]
+    async fn test_weighted_round_robin() {
+        let (a, b) = (server(200, "a").await, server(200, "b").await);
+        let provider = FailoverProvider::<TestTarget>::new(
+            vec![Endpoint::weighted(a.url(), 3), Endpoint::new(b.url())],
+            RoutingStrategy::RoundRobin,
+        );
+
+        for _ in 0..8 {
+            provider.request_json::<String>(ping(HTTPMethod::GET)).await.unwrap();
+        }
+        assert_eq!((a.received_requests().len(), b.received_requests().len()), (6, 2));
+    }
+
+    #[tokio::test]
+    async fn test_all_endpoints_failing_returns_last_response() {
+        let (a, b) = (server(500, "a").await, server(502, "b").await);
+        let provider =
+            FailoverProvider::<TestTarget>::new(vec![Endpoint::new(a.url()), Endpoint::new(b.url())], RoutingStrategy::Priority);
+
+        let response = provider.request(ping(HTTPMethod::GET)).await.unwrap();
+        assert_eq!(response.status(), 502);
+    }
+
+    #[tokio::test]
+    async fn test_non_idempotent_requests_are_not_resent() {
+        let (down, up) = (server(503, "down").await, server(200, "up").await);
+        let provider =
+            FailoverProvider::<TestTarget>::new(vec![Endpoint::new(down.url()), Endpoint::new(up.url())], RoutingStrategy::Priority);
+
+        assert_eq!(provider.request(ping(HTTPMethod::POST)).await.unwrap().status(), 503);
+        assert_eq!(up.received_requests().len(), 0);
+
+        let provider = provider.with_idempotent_fn(|_| true);
+        assert_eq!(provider.request_json::<String>(ping(HTTPMethod::POST)).await.unwrap(), "up");
+    }
+
+    #[tokio::test]
+    async fn test_failures_do_not_count_as_latency() {
+        let slow_up = test_util::serve(|_| {
+            std::thread::sleep(Duration::from_millis(30));
+            (200, "\"up\"".into())
+        })
+        .await;
+        let fast_down = server(503, "down").await;
+        let provider = FailoverProvider::<TestTarget>::new(
+            vec![Endpoint::new(slow_up.url()), Endpoint::new(fast_down.url())],
+            RoutingStrategy::LeastLatency,
+        );
+
+        // the untried endpoint goes first, fails, and must not look faster than the working one
+        for _ in 0..3 {
+            assert_eq!(provider.request_json::<String>(ping(HTTPMethod::GET)).await.unwrap(), "up");
+        }
+        assert_eq!(fast_down.received_requests().len(), 1);
+    }
+}
@@ -0,0 +1,308 @@
+//! Hedged requests for latency-sensitive, idempotent reads.
+use crate::{
+    error::Error,
+    http::HTTPResponse,
+    target::{Target, is_safe_method},
+};
+use futures::future::{Either, select};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    pin::pin,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Number of recent latencies kept to compute percentile delays.
+const LATENCY_SAMPLES: usize = 128;
+/// Samples needed before a percentile delay is trusted over the initial delay.
+const MIN_SAMPLES: usize = 10;
+
+/// How long to wait for the first attempt before sending the hedge.
+#[derive(Debug, Clone, Copy)]
+pub enum HedgeDelay {
+    Fixed(Duration),
+    /// The given percentile (0.0 - 1.0, e.g. `0.95`) of recently observed latencies,
+    /// using `initial` until enough requests have been observed.
+    Percentile { percentile: f64, initial: Duration },
+}
+
+/// Opt-in hedging policy for a `Provider`, see `Provider::with_hedging`.
+///
+/// If an idempotent request hasn't answered within the hedge delay, a second copy is sent
+/// (optionally to another endpoint); the first successful response wins and the other
+/// request is cancelled. An error or a server error (5xx) response doesn't win: the other
+/// attempt is awaited, and the failure is only returned if both attempts failed.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let policy = HedgePolicy::new(HedgeDelay::Percentile { percentile: 0.95, initial: Duration::from_millis(100) })
+///     .with_alternate_base_url("https://replica.example.com")?;
+/// let provider = Provider::<Api>::default().with_hedging(policy);
+/// ```
+pub struct HedgePolicy<T> {
+    delay: HedgeDelay,
+    is_idempotent: fn(&T) -> bool,
+    alternate_base_url: Option<reqwest::Url>,
+    latencies: Mutex<VecDeque<Duration>>,
+}
+
+impl<T> std::fmt::Debug for HedgePolicy<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("HedgePolicy")
+            .field("delay", &self.delay)
+            .field("alternate_base_url", &self.alternate_base_url.as_ref().map(|url| url.as_str()))
+            .finish()
+    }
+}
+
+impl<T: Target> HedgePolicy<T> {
+    /// Hedges GET, HEAD and OPTIONS requests.
+    pub fn new(delay: HedgeDelay) -> Self {
+        Self {
+            delay,
+            is_idempotent: is_safe_method,
+            alternate_base_url: None,
+            latencies: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Decides which targets are safe to send twice, e.g. read-only JSON-RPC methods sent with POST.
+    pub fn with_idempotent_fn(mut self, is_idempotent: fn(&T) -> bool) -> Self {
+        self.is_idempotent = is_idempotent;
+        self
+    }
+
+    /// Sends the hedge to another replica: the scheme, host and port of `base_url` replace the
+    /// original ones, and the request path is kept as is. The replica must serve the API under
+    /// the same path, so a `base_url` with a path (`https://replica.example/v1`) is rejected with
+    /// `Error::InvalidUrl`, like one that can't be parsed.
+    pub fn with_alternate_base_url(mut self, base_url: &str) -> Result<Self, Error> {
+        let invalid = |reason: String| Error::InvalidUrl {
+            url: base_url.to_string(),
+            reason,
+        };
+        let url = reqwest::Url::parse(base_url).map_err(|e| invalid(e.to_string()))?;
+        if url.cannot_be_a_base() || url.host_str().is_none() {
+            return Err(invalid("not a base URL".into()));
+        }
+        if url.path() != "/" {
+            return Err(invalid("only the scheme, host and port of an alternate base URL are used, it can't have a path".into()));
+        }
+        self.alternate_base_url = Some(url);
+        Ok(self)
+    }
+
+    pub(crate) fn applies_to(&self, target: &T) -> bool {
+        (self.is_idempotent)(target)
+    }
+
+    /// The current hedge delay.
+    pub fn delay(&self) -> Duration {
+        match self.delay {
+            HedgeDelay::Fixed(delay) => delay,
+            HedgeDelay::Percentile { percentile, initial } => {
+                let latencies = self.latencies.lock().unwrap();
+                if latencies.len() < MIN_SAMPLES {
+                    return initial;
+                }
+                let mut sorted = latencies.iter().copied().collect::<Vec<_>>();
+                sorted.sort();
+                let rank = (percentile.clamp(0.0, 1.0) * sorted.len() as f64).ceil() as usize;
+                sorted[rank.clamp(1, sorted.len()) - 1]
+            }
+        }
+    }
+
+    fn observe(&self, latency: Duration) {
+        let mut latencies = self.latencies.lock().unwrap();
+        latencies.push_back(latency);
+        if latencies.len() > LATENCY_SAMPLES {
+            latencies.pop_front();
+        }
+    }
+
+    fn hedge_request(&self, request: &reqwest::Request) -> Option<reqwest::Request> {
+        let mut hedge = request.try_clone()?;
+        if let Some(alternate) = &self.alternate_base_url {
+            let url = hedge.url_mut();
+            url.set_scheme(alternate.scheme()).ok()?;
+            url.set_host(alternate.host_str()).ok()?;
+            url.set_port(alternate.port()).ok()?;
+        }
+        Some(hedge)
+    }
+
+    /// Runs `request`, racing a hedged copy against it once the delay has passed.
+    pub(crate) async fn execute<F, Fut>(&self, request: reqwest::Request, execute: F) -> Result<HTTPResponse, Error>
+    where
+        F: Fn(reqwest::Request) -> Fut,
+        Fut: Future<Output = Result<HTTPResponse, Error>>,
+    {
+        let Some(hedge) = self.hedge_request(&request) else {
+            return execute(request).await;
+        };
+
+        let started = Instant::now();
+        let mut primary = pin!(execute(request));
+        if let Either::Left((result, _)) = select(primary.as_mut(), pin!(tokio::time::sleep(self.delay()))).await {
+            self.observe(started.elapsed());
+            return result;
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(url = %hedge.url(), "sending hedged request");
+        let secondary = pin!(execute(hedge));
+        let (first, other) = match select(primary, secondary).await {
+            Either::Left(done) | Either::Right(done) => done,
+        };
+        let result = if failed(&first) {
+            // a failed attempt doesn't win the race: the other one decides, and a server error
+            // response is only returned if both attempts failed
+            match (first, other.await) {
+                (Ok(response), Err(_)) => Ok(response),
+                (_, second) => second,
+            }
+        } else {
+            first
+        };
+        // Always the primary's latency, so the percentile tracks the backend rather than the hedges.
+        // If the hedge won, the primary took at least this long.
+        self.observe(started.elapsed());
+        result
+    }
+}
+
+/// Errors and server error responses, like the circuit breaker counts them.
+fn failed(result: &Result<HTTPResponse, Error>) -> bool {
+    !matches!(result, Ok(response) if !response.status().is_server_error())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        provider::{JsonProviderType, Provider},
+        test_util::{self, TestTarget},
+        testing::MockServer,
+    };
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    /// The first request is slow, later ones answer immediately with their sequence number.
+    async fn slow_first_server() -> MockServer {
+        let count = Arc::new(AtomicUsize::new(0));
+        test_util::serve(move |_| {
+            let n = count.fetch_add(1, Ordering::SeqCst);
+            if n == 0 {
+                std::thread::sleep(Duration::from_millis(500));
+            }
+            (200, n.to_string())
+        })
+        .await
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_slow_read_is_hedged() {
+        let server = slow_first_server().await;
+        let provider = Provider::<TestTarget>::default().with_hedging(HedgePolicy::new(HedgeDelay::Fixed(Duration::from_millis(50))));
+
+        let started = Instant::now();
+        let answer: usize = provider.request_json(TestTarget::get(server.url(), "/")).await.unwrap();
+        assert_eq!(answer, 1);
+        assert!(started.elapsed() < Duration::from_millis(400));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_writes_are_not_hedged() {
+        let server = slow_first_server().await;
+        let provider = Provider::<TestTarget>::default().with_hedging(HedgePolicy::new(HedgeDelay::Fixed(Duration::from_millis(50))));
+
+        let answer: usize = provider.request_json(TestTarget::post(server.url(), "/")).await.unwrap();
+        assert_eq!(answer, 0);
+        assert_eq!(server.received_requests().len(), 1);
+    }
+
+    #[test]
+    fn test_percentile_delay() {
+        let policy = HedgePolicy::<TestTarget>::new(HedgeDelay::Percentile {
+            percentile: 0.9,
+            initial: Duration::from_millis(7),
+        });
+        assert_eq!(policy.delay(), Duration::from_millis(7));
+
+        for ms in 1..=20 {
+            policy.observe(Duration::from_millis(ms));
+        }
+        assert_eq!(policy.delay(), Duration::from_millis(18));
+    }
+
+    #[tokio::test]
+    async fn test_hedge_win_records_primary_latency() {
+        let policy = HedgePolicy::<TestTarget>::new(HedgeDelay::Percentile {
+            percentile: 0.5,
+            initial: Duration::from_millis(50),
+        });
+        let calls = AtomicUsize::new(0);
+        // the primary hangs, the hedge answers at once
+        let execute = |_| {
+            let primary = calls.fetch_add(1, Ordering::SeqCst) == 0;
+            async move {
+                if primary {
+                    tokio::time::sleep(Duration::from_secs(5)).await;
+                }
+                Ok::<_, Error>(HTTPResponse::from(http::Response::new(String::new())))
+            }
+        };
+
+        let request = reqwest::Client::new().get("http://primary.example/items").build().unwrap();
+        policy.execute(request, execute).await.unwrap();
+        let latencies = policy.latencies.lock().unwrap().clone();
+        assert_eq!(latencies.len(), 1);
+        assert!(latencies[0] >= Duration::from_millis(50), "{:?}", latencies);
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_do_not_win() {
+        let policy = HedgePolicy::<TestTarget>::new(HedgeDelay::Fixed(Duration::from_millis(10)));
+        let respond = |primary_status: u16, hedge_status: u16| {
+            let calls = AtomicUsize::new(0);
+            move |_| {
+                let primary = calls.fetch_add(1, Ordering::SeqCst) == 0;
+                async move {
+                    // the primary fails quickly after the hedge was sent, the hedge answers later
+                    let (delay, status) = if primary { (30, primary_status) } else { (100, hedge_status) };
+                    tokio::time::sleep(Duration::from_millis(delay)).await;
+                    let mut response = http::Response::new(String::new());
+                    *response.status_mut() = http::StatusCode::from_u16(status).unwrap();
+                    Ok::<_, Error>(HTTPResponse::from(response))
+                }
+            }
+        };
+        let request = || reqwest::Client::new().get("http://primary.example/items").build().unwrap();
+
+        let response = policy.execute(request(), respond(503, 200)).await.unwrap();
+        assert_eq!(response.status(), 200);
+        let response = policy.execute(request(), respond(503, 502)).await.unwrap();
+        assert_eq!(response.status(), 502);
+    }
+
+    #[test]
+    fn test_alternate_base_url() {
+        let policy = HedgePolicy::<TestTarget>::new(HedgeDelay::Fixed(Duration::from_millis(10)));
+        let request = reqwest::Client::new().get("http://primary.example:8080/v1/items?x=1").build().unwrap();
+
+        let policy = policy.with_alternate_base_url("https://replica.example").unwrap();
+        assert_eq!(policy.hedge_request(&request).unwrap().url().as_str(), "https://replica.example/v1/items?x=1");
+
+        for base_url in ["not a url", "mailto:ops@example.com", "https://replica.example/v1"] {
+            let err = HedgePolicy::<TestTarget>::new(HedgeDelay::Fixed(Duration::from_millis(10)))
+                .with_alternate_base_url(base_url)
+                .unwrap_err();
+            assert!(matches!(err, Error::InvalidUrl { .. }), "{}", err);
+        }
+    }
+}
@@ -14,6 +14,7 @@ impl HTTPBody {
 
 pub type HTTPResponse = reqwest::Response;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HTTPMethod {
     GET,
     POST,
@@ -79,7 +80,7 @@ pub enum AuthMethod {
     /// Takes a token (`String`).
     Bearer(String),
     /// Custom authentication logic provided as a closure.
-    /// The closure takes a `reqwest::RequestBuilder` and returns a modified `reqwest::RequestBuilder`.
+    /// The closure takes a `ProviderRequestBuilder` and returns a modified `ProviderRequestBuilder`.
     /// This allows for flexible and complex authentication mechanisms.
     /// 
     /// # Example
@@ -1,23 +1,53 @@
-use serde::{Deserialize, Serialize};
+use crate::{
+    http::{AuthMethod, HTTPBody, HTTPMethod},
+    target::{JsonRpcTarget, Target},
+};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use serde_json::Value;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::atomic::{AtomicU64, Ordering},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JsonRpcRequest {
-    pub jsonrpc: &'static str,
-    pub id: JsonRpcId,
-    pub method: &'static str,
+    pub jsonrpc: Cow<'static, str>,
+    /// `None` for notifications, which the server must not answer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<JsonRpcId>,
+    pub method: Cow<'static, str>,
     pub params: Vec<Value>,
 }
 
 impl JsonRpcRequest {
-    pub fn new(method: &'static str, params: Vec<Value>, id: u64) -> Self {
+    pub fn new(method: impl Into<Cow<'static, str>>, params: Vec<Value>, id: u64) -> Self {
+        Self::with_id(method, params, JsonRpcId::Integer(id))
+    }
+
+    /// Creates a request with an arbitrary id, e.g. one produced by an `IdGenerator`.
+    pub fn with_id(method: impl Into<Cow<'static, str>>, params: Vec<Value>, id: JsonRpcId) -> Self {
+        Self {
+            jsonrpc: Cow::Borrowed("2.0"),
+            id: Some(id),
+            method: method.into(),
+            params,
+        }
+    }
+
+    /// Creates a notification: a request without an id, for which no response is expected.
+    pub fn notification(method: impl Into<Cow<'static, str>>, params: Vec<Value>) -> Self {
         Self {
-            jsonrpc: "2.0",
-            id: JsonRpcId::Integer(id),
-            method,
+            jsonrpc: Cow::Borrowed("2.0"),
+            id: None,
+            method: method.into(),
             params,
         }
     }
+
+    pub fn is_notification(&self) -> bool {
+        self.id.is_none()
+    }
 }
 
 impl From<JsonRpcRequest> for reqwest::Body {
@@ -26,34 +56,54 @@ impl From<JsonRpcRequest> for reqwest::Body {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonRpcResponse<T> {
     pub id: JsonRpcId,
     pub jsonrpc: String,
     pub result: T,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonRpcErrorResponse {
     pub jsonrpc: String,
     pub id: JsonRpcId,
     pub error: JsonRpcError,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JsonRpcError {
     pub code: i64,
     pub message: String,
 }
 
+/// Constructors for the error codes defined by the JSON-RPC 2.0 specification.
+impl JsonRpcError {
+    pub fn parse_error(message: impl Into<String>) -> Self {
+        Self { code: -32700, message: message.into() }
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self { code: -32600, message: message.into() }
+    }
+
+    pub fn method_not_found(method: &str) -> Self {
+        Self { code: -32601, message: format!("Method not found: {}", method) }
+    }
+
+    pub fn invalid_params(message: impl Into<String>) -> Self {
+        Self { code: -32602, message: message.into() }
+    }
+
+    pub fn internal_error(message: impl Into<String>) -> Self {
+        Self { code: -32603, message: message.into() }
+    }
+}
+
 impl std::error::Error for JsonRpcError {}
 
 impl From<reqwest::Error> for JsonRpcError {
     fn from(err: reqwest::Error) -> Self {
-        JsonRpcError {
-            code: -32603,
-            message: format!("Internal error ({})", err),
-        }
+        JsonRpcError::internal_error(format!("Internal error ({})", err))
     }
 }
 
@@ -65,14 +115,17 @@ impl From<crate::Error> for JsonRpcError {
                 e.into()
             }
             #[cfg(feature = "middleware")]
-            crate::Error::ReqwestMiddleware(e) => JsonRpcError {
-                code: -32603, // Internal error
-                message: format!("Middleware error: {}", e),
-            },
-            crate::Error::SerdeJson(e) => JsonRpcError {
-                code: -32603, // Internal error (could also be Parse error -32700 depending on context)
-                message: format!("Serialization/deserialization error: {}", e),
-            },
+            crate::Error::ReqwestMiddleware(e) => JsonRpcError::internal_error(format!("Middleware error: {}", e)),
+            // could also be a parse error (-32700) depending on context
+            crate::Error::SerdeJson(e) => {
+                JsonRpcError::internal_error(format!("Serialization/deserialization error: {}", e))
+            }
+            crate::Error::Query(_)
+            | crate::Error::InvalidUrl { .. }
+            | crate::Error::CircuitOpen { .. }
+            | crate::Error::NoEndpoints
+            | crate::Error::Transport(_) => JsonRpcError::internal_error(err.to_string()),
+            crate::Error::Shared(e) => JsonRpcError::internal_error(e.to_string()),
         }
     }
 }
@@ -83,16 +136,393 @@ impl std::fmt::Display for JsonRpcError {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Limits for `JsonRpcProviderType::batch_auto`, typically mirroring the node's configuration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchLimits {
+    /// Maximum number of calls per batch.
+    pub max_items: usize,
+    /// Maximum serialized size of a batch request body, in bytes.
+    pub max_bytes: usize,
+}
+
+impl Default for BatchLimits {
+    /// 1000 calls or 5 MB per batch.
+    fn default() -> Self {
+        Self {
+            max_items: 1000,
+            max_bytes: 5 * 1024 * 1024,
+        }
+    }
+}
+
+impl BatchLimits {
+    /// Splits serialized request sizes into consecutive index ranges that fit both limits.
+    /// A single request larger than `max_bytes` still gets a range of its own.
+    pub(crate) fn split(&self, sizes: &[usize], max_items: usize) -> Vec<std::ops::Range<usize>> {
+        let max_items = max_items.min(self.max_items).max(1);
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        // `[` + `]`, plus one `,` between entries
+        let mut bytes = 2;
+        for (idx, size) in sizes.iter().enumerate() {
+            let separator = usize::from(idx > start);
+            if idx > start && (idx - start == max_items || bytes + separator + size > self.max_bytes) {
+                ranges.push(start..idx);
+                start = idx;
+                bytes = 2;
+            }
+            bytes += usize::from(idx > start) + size;
+        }
+        if start < sizes.len() {
+            ranges.push(start..sizes.len());
+        }
+        ranges
+    }
+}
+
+/// Phrases nodes use in the single error object returned for an oversized batch, e.g. geth's
+/// "batch too large" or erigon's "batch limit 100 exceeded".
+const BATCH_TOO_LARGE_MESSAGES: [&str; 5] = ["batch too large", "batch too big", "batch size", "batch limit", "too many requests in batch"];
+
+/// Whether a batch response means the batch exceeded the server's limits: HTTP 413, or a single
+/// error object (instead of an array) explicitly about the batch size. Other errors mentioning a
+/// limit, like rate or gas limits, are not size rejections.
+pub(crate) fn is_batch_too_large(status: u16, body: &[u8]) -> bool {
+    if status == 413 {
+        return true;
+    }
+    match serde_json::from_slice::<JsonRpcErrorResponse>(body) {
+        Ok(response) => {
+            let message = response.error.message.to_lowercase();
+            BATCH_TOO_LARGE_MESSAGES.iter().any(|needle| message.contains(needle))
+        }
+        Err(_) => false,
+    }
+}
+
+/// Outcome of one chunk sent by `JsonRpcProviderType::batch_chunk_outcomes`.
+#[derive(Debug)]
+pub struct ChunkOutcome<T, U> {
+    /// Position of the chunk's first target in the original input.
+    pub offset: usize,
+    /// The targets sent in this chunk, handed back so failed chunks can be retried.
+    pub targets: Vec<T>,
+    pub result: Result<Vec<JsonRpcResult<U>>, JsonRpcError>,
+}
+
+impl<T, U> ChunkOutcome<T, U> {
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum JsonRpcResult<T> {
     Value(JsonRpcResponse<T>),
     Error(JsonRpcErrorResponse),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl<T> JsonRpcResult<T> {
+    /// The id of the request this result answers.
+    pub fn id(&self) -> &JsonRpcId {
+        match self {
+            JsonRpcResult::Value(response) => &response.id,
+            JsonRpcResult::Error(response) => &response.id,
+        }
+    }
+}
+
+impl JsonRpcResult<Value> {
+    /// Deserializes an untyped result into `JsonRpcResult<U>`, leaving error responses as they are.
+    pub fn deserialize<U: DeserializeOwned>(self) -> Result<JsonRpcResult<U>, serde_json::Error> {
+        Ok(match self {
+            JsonRpcResult::Value(response) => JsonRpcResult::Value(JsonRpcResponse {
+                id: response.id,
+                jsonrpc: response.jsonrpc,
+                result: serde_json::from_value(response.result)?,
+            }),
+            JsonRpcResult::Error(response) => JsonRpcResult::Error(response),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum JsonRpcId {
     Integer(u64),
     String(String),
+    /// Used by servers in error responses when the request id could not be determined.
+    Null,
+}
+
+impl From<u64> for JsonRpcId {
+    fn from(id: u64) -> Self {
+        JsonRpcId::Integer(id)
+    }
+}
+
+impl From<String> for JsonRpcId {
+    fn from(id: String) -> Self {
+        JsonRpcId::String(id)
+    }
+}
+
+impl From<&str> for JsonRpcId {
+    fn from(id: &str) -> Self {
+        JsonRpcId::String(id.to_string())
+    }
+}
+
+impl std::fmt::Display for JsonRpcId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JsonRpcId::Integer(id) => write!(f, "{}", id),
+            JsonRpcId::String(id) => write!(f, "{}", id),
+            JsonRpcId::Null => write!(f, "null"),
+        }
+    }
+}
+
+/// Generic passthrough JSON-RPC target whose method is only known at runtime,
+/// e.g. for proxies forwarding arbitrary calls or namespaced plugin methods.
+///
+/// `call`, `notify` and the batch methods build the request body themselves, with an id from the
+/// provider's `IdGenerator`. `body()` is only used by the plain `ProviderType::request` path,
+/// where it takes ids from a process-wide `SequentialIdGenerator` instead.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let target = DynamicRpc::new("https://rpc.example.com", format!("{}_getInfo", namespace), vec![]);
+/// let result: JsonRpcResult<Value> = provider.call(target).await?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct DynamicRpc {
+    /// Full endpoint URL the call is posted to.
+    pub url: String,
+    pub method: Cow<'static, str>,
+    pub params: Vec<Value>,
+    pub headers: HashMap<String, String>,
+    pub notification: bool,
+}
+
+impl DynamicRpc {
+    pub fn new(url: impl Into<String>, method: impl Into<Cow<'static, str>>, params: Vec<Value>) -> Self {
+        Self {
+            url: url.into(),
+            method: method.into(),
+            params,
+            headers: HashMap::from([("Content-Type".to_string(), "application/json".to_string())]),
+            notification: false,
+        }
+    }
+
+    /// Adds a header sent with this call, e.g. an API key expected by the node.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Marks this call as a notification (no id, no response expected).
+    pub fn as_notification(mut self) -> Self {
+        self.notification = true;
+        self
+    }
+}
+
+impl Target for DynamicRpc {
+    fn base_url(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.url)
+    }
+
+    fn method(&self) -> HTTPMethod {
+        HTTPMethod::POST
+    }
+
+    fn path(&self) -> String {
+        String::new()
+    }
+
+    fn query(&self) -> HashMap<String, String> {
+        HashMap::default()
+    }
+
+    fn headers(&self) -> HashMap<String, String> {
+        self.headers.clone()
+    }
+
+    fn authentication(&self) -> Option<AuthMethod> {
+        None
+    }
+
+    fn body(&self) -> Result<HTTPBody, crate::Error> {
+        let request = if self.notification {
+            JsonRpcRequest::notification(self.method.clone(), self.params.clone())
+        } else {
+            static IDS: SequentialIdGenerator = SequentialIdGenerator::starting_at(1);
+            JsonRpcRequest::with_id(self.method.clone(), self.params.clone(), IDS.next_id())
+        };
+        Ok(HTTPBody::from(&request)?)
+    }
+}
+
+impl JsonRpcTarget for DynamicRpc {
+    fn method_name(&self) -> Cow<'static, str> {
+        self.method.clone()
+    }
+
+    fn params(&self) -> Vec<Value> {
+        self.params.clone()
+    }
+
+    fn is_notification(&self) -> bool {
+        self.notification
+    }
+}
+
+/// Produces ids for outgoing JSON-RPC requests.
+///
+/// A `Provider` owns one generator and uses it for every `call`, `batch` and `batch_chunk_by`,
+/// so ids stay unique across concurrent calls sharing the same connection or proxy.
+/// Any `Fn() -> JsonRpcId` closure is also a generator.
+pub trait IdGenerator: Send + Sync {
+    fn next_id(&self) -> JsonRpcId;
+}
+
+impl<F> IdGenerator for F
+where
+    F: Fn() -> JsonRpcId + Send + Sync,
+{
+    fn next_id(&self) -> JsonRpcId {
+        self()
+    }
+}
+
+/// Default generator: an atomic, monotonically increasing integer counter starting at 1.
+#[derive(Debug)]
+pub struct SequentialIdGenerator {
+    next: AtomicU64,
+}
+
+impl SequentialIdGenerator {
+    /// Creates a generator whose first id is `start`.
+    pub const fn starting_at(start: u64) -> Self {
+        Self {
+            next: AtomicU64::new(start),
+        }
+    }
+}
+
+impl Default for SequentialIdGenerator {
+    fn default() -> Self {
+        Self::starting_at(1)
+    }
+}
+
+impl IdGenerator for SequentialIdGenerator {
+    fn next_id(&self) -> JsonRpcId {
+        JsonRpcId::Integer(self.next.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Generates random UUID v4 ids as `JsonRpcId::String`.
+#[cfg(feature = "uuid")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct UuidIdGenerator;
+
+#[cfg(feature = "uuid")]
+impl IdGenerator for UuidIdGenerator {
+    fn next_id(&self) -> JsonRpcId {
+        JsonRpcId::String(uuid::Uuid::new_v4().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sequential_id_generator() {
+        let generator = SequentialIdGenerator::default();
+        assert_eq!(generator.next_id(), JsonRpcId::Integer(1));
+        assert_eq!(generator.next_id(), JsonRpcId::Integer(2));
+
+        let generator = SequentialIdGenerator::starting_at(100);
+        assert_eq!(generator.next_id(), JsonRpcId::Integer(100));
+    }
+
+    #[test]
+    fn test_closure_id_generator() {
+        let generator = || JsonRpcId::from("req-1");
+        assert_eq!(generator.next_id().to_string(), "req-1");
+    }
+
+    #[test]
+    fn test_notification_has_no_id() {
+        let notification = JsonRpcRequest::notification("log", vec![Value::from("hello")]);
+        assert!(notification.is_notification());
+        assert_eq!(
+            serde_json::to_value(&notification).unwrap(),
+            serde_json::json!({"jsonrpc": "2.0", "method": "log", "params": ["hello"]})
+        );
+    }
+
+    #[test]
+    fn test_request_roundtrip_with_runtime_method() {
+        let method = format!("{}_{}", "plugin", "status");
+        let request = JsonRpcRequest::new(method, vec![Value::from(1)], 7);
+        let json = serde_json::to_string(&request).unwrap();
+
+        let parsed: JsonRpcRequest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.method, "plugin_status");
+        assert_eq!(parsed.id, Some(JsonRpcId::Integer(7)));
+    }
+
+    #[test]
+    fn test_dynamic_rpc_body() {
+        let target = DynamicRpc::new("http://localhost:8545", String::from("net_version"), vec![]);
+        assert_eq!(target.method_name(), "net_version");
+
+        let body: Value = serde_json::from_slice(&target.body().unwrap().to_bytes()).unwrap();
+        assert_eq!(body["method"], "net_version");
+
+        let next: Value = serde_json::from_slice(&target.body().unwrap().to_bytes()).unwrap();
+        assert_ne!(body["id"], next["id"]);
+    }
+
+    #[test]
+    fn test_batch_limits_split() {
+        let limits = BatchLimits { max_items: 3, max_bytes: 20 };
+        // 2 + 5 + 1 + 5 + 1 + 5 = 19 bytes fit, the next entry would not
+        assert_eq!(limits.split(&[5, 5, 5, 5], usize::MAX), vec![0..3, 3..4]);
+        assert_eq!(limits.split(&[8, 8, 8], usize::MAX), vec![0..2, 2..3]);
+        assert_eq!(limits.split(&[50, 1], usize::MAX), vec![0..1, 1..2]);
+        assert_eq!(limits.split(&[1, 1, 1, 1], 2), vec![0..2, 2..4]);
+    }
+
+    #[test]
+    fn test_is_batch_too_large() {
+        assert!(is_batch_too_large(413, b""));
+        assert!(is_batch_too_large(
+            200,
+            br#"{"jsonrpc":"2.0","id":null,"error":{"code":-32600,"message":"batch too large"}}"#
+        ));
+        assert!(is_batch_too_large(
+            200,
+            br#"{"jsonrpc":"2.0","id":null,"error":{"code":-32600,"message":"Batch limit 100 exceeded"}}"#
+        ));
+        assert!(!is_batch_too_large(200, br#"[{"jsonrpc":"2.0","id":1,"result":"0x1"}]"#));
+        for message in ["rate limit exceeded", "gas limit reached", "request exceeds block range"] {
+            let body = format!(r#"{{"jsonrpc":"2.0","id":null,"error":{{"code":-32005,"message":"{}"}}}}"#, message);
+            assert!(!is_batch_too_large(200, body.as_bytes()), "{}", message);
+        }
+    }
+
+    #[test]
+    fn test_result_id() {
+        let result: JsonRpcResult<String> =
+            serde_json::from_str(r#"{"jsonrpc":"2.0","id":"abc","result":"0x1"}"#).unwrap();
+        assert_eq!(result.id(), &JsonRpcId::String("abc".into()));
+    }
 }
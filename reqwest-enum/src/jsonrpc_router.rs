@@ -0,0 +1,382 @@
+//! Local JSON-RPC server dispatcher, the inverse of the client: parses requests and batches back
+//! into a `JsonRpcTarget` and answers them with a user handler.
+use crate::{
+    error::Error,
+    jsonrpc::{JsonRpcError, JsonRpcErrorResponse, JsonRpcId, JsonRpcResponse, JsonRpcResult},
+    transport::{Body, Transport},
+};
+use bytes::Bytes;
+use futures::future::{BoxFuture, join_all};
+use serde_json::{Map, Value};
+use std::{future::Future, sync::Arc};
+
+/// Reconstructs a target from a JSON-RPC method and its params, the inverse of
+/// `JsonRpcTarget::method_name` and `JsonRpcTarget::params`.
+///
+/// ```rust,ignore
+/// impl FromJsonRpc for EthereumRPC {
+///     fn from_json_rpc(method: &str, params: Vec<Value>) -> Result<Self, JsonRpcError> {
+///         match method {
+///             "eth_chainId" => Ok(EthereumRPC::ChainId),
+///             "eth_getBalance" => Ok(EthereumRPC::GetBalance(param(&params, 0)?)),
+///             _ => Err(JsonRpcError::method_not_found(method)),
+///         }
+///     }
+/// }
+/// ```
+pub trait FromJsonRpc: Sized {
+    fn from_json_rpc(method: &str, params: Vec<Value>) -> Result<Self, JsonRpcError>;
+
+    /// Reconstructs a target from by-name params (a JSON object, JSON-RPC 2.0 §4.2). Methods only
+    /// taking positional params can keep the default, which answers "Invalid params".
+    fn from_json_rpc_named(method: &str, params: Map<String, Value>) -> Result<Self, JsonRpcError> {
+        let _ = params;
+        Err(JsonRpcError::invalid_params(format!("Invalid params: {} expects positional params", method)))
+    }
+}
+
+/// Deserializes the param at `index`, failing with an "Invalid params" error.
+pub fn param<U: serde::de::DeserializeOwned>(params: &[Value], index: usize) -> Result<U, JsonRpcError> {
+    let value = params.get(index).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| JsonRpcError::invalid_params(format!("param {}: {}", index, e)))
+}
+
+/// Deserializes the by-name param `name`, failing with an "Invalid params" error.
+pub fn named_param<U: serde::de::DeserializeOwned>(params: &Map<String, Value>, name: &str) -> Result<U, JsonRpcError> {
+    let value = params.get(name).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| JsonRpcError::invalid_params(format!("param {}: {}", name, e)))
+}
+
+type Handler<T> = dyn Fn(T) -> BoxFuture<'static, Result<Value, JsonRpcError>> + Send + Sync;
+
+/// A call's id (`None` for notifications), method and params.
+type Call = (Option<JsonRpcId>, String, Params);
+
+/// The id of a rejected call, if it could be read, and the error to answer with.
+type CallError = (Option<JsonRpcId>, JsonRpcError);
+
+enum Params {
+    Positional(Vec<Value>),
+    Named(Map<String, Value>),
+}
+
+/// Dispatches JSON-RPC requests (single or batched) to a handler, producing spec-compliant responses:
+/// notifications are not answered, and malformed requests get parse / invalid request errors.
+/// Positional params go to `FromJsonRpc::from_json_rpc`, by-name params to
+/// `FromJsonRpc::from_json_rpc_named`.
+///
+/// Mount `handle_http` on a local hyper or axum server, or use the router directly as the
+/// `Transport` of a `Provider` so client tests run offline:
+///
+/// ```rust,ignore
+/// let router = JsonRpcRouter::new(|target: EthereumRPC| async move {
+///     match target {
+///         EthereumRPC::ChainId => Ok(json!("0x1")),
+///         _ => Err(JsonRpcError::method_not_found("unsupported")),
+///     }
+/// });
+/// let provider = Provider::<EthereumRPC>::default().with_transport(router);
+/// ```
+pub struct JsonRpcRouter<T> {
+    handler: Arc<Handler<T>>,
+}
+
+impl<T> Clone for JsonRpcRouter<T> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for JsonRpcRouter<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JsonRpcRouter").field("handler", &"<function>").finish()
+    }
+}
+
+impl<T: FromJsonRpc + Send + 'static> JsonRpcRouter<T> {
+    pub fn new<F, Fut>(handler: F) -> Self
+    where
+        F: Fn(T) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Value, JsonRpcError>> + Send + 'static,
+    {
+        Self {
+            handler: Arc::new(move |target| Box::pin(handler(target))),
+        }
+    }
+
+    /// Handles a request body; `None` if there is nothing to answer (only notifications).
+    /// Calls of a batch are handled concurrently and answered in request order.
+    pub async fn handle(&self, body: &[u8]) -> Option<Value> {
+        let message = match serde_json::from_slice::<Value>(body) {
+            Ok(message) => message,
+            Err(e) => return Some(error_response(JsonRpcId::Null, JsonRpcError::parse_error(format!("Parse error: {}", e)))),
+        };
+
+        match message {
+            Value::Array(calls) if calls.is_empty() => {
+                Some(error_response(JsonRpcId::Null, JsonRpcError::invalid_request("Invalid Request: empty batch")))
+            }
+            Value::Array(calls) => {
+                let responses = join_all(calls.into_iter().map(|call| self.dispatch(call))).await;
+                let responses = responses.into_iter().flatten().collect::<Vec<_>>();
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            call => self.dispatch(call).await,
+        }
+    }
+
+    /// Handles an HTTP request: `200` with the response body, or `204` if there is nothing to answer.
+    pub async fn handle_http(&self, request: http::Request<Bytes>) -> http::Response<Bytes> {
+        let Some(body) = self.handle(request.body()).await else {
+            let mut response = http::Response::new(Bytes::new());
+            *response.status_mut() = http::StatusCode::NO_CONTENT;
+            return response;
+        };
+        let mut response = http::Response::new(Bytes::from(body.to_string()));
+        response
+            .headers_mut()
+            .insert(http::header::CONTENT_TYPE, http::HeaderValue::from_static("application/json"));
+        response
+    }
+
+    /// Handles one call; `None` for notifications.
+    async fn dispatch(&self, call: Value) -> Option<Value> {
+        let (id, method, params) = match parse_call(call) {
+            Ok(call) => call,
+            Err((id, error)) => return Some(error_response(id.unwrap_or(JsonRpcId::Null), error)),
+        };
+
+        let target = match params {
+            Params::Positional(params) => T::from_json_rpc(&method, params),
+            Params::Named(params) => T::from_json_rpc_named(&method, params),
+        };
+        let result = match target {
+            Ok(target) => (self.handler)(target).await,
+            Err(error) => Err(error),
+        };
+
+        let id = id?;
+        Some(match result {
+            Ok(result) => serde_json::to_value(JsonRpcResult::Value(JsonRpcResponse {
+                id,
+                jsonrpc: "2.0".into(),
+                result,
+            }))
+            .expect("serializable response"),
+            Err(error) => error_response(id, error),
+        })
+    }
+}
+
+impl<T: FromJsonRpc + Send + 'static> Transport for JsonRpcRouter<T> {
+    async fn execute(&self, request: http::Request<Bytes>) -> Result<http::Response<Body>, Error> {
+        Ok(self.handle_http(request).await.map(Body::from))
+    }
+}
+
+/// Validates a call object, returning its id (`None` for notifications), method and params.
+fn parse_call(call: Value) -> Result<Call, CallError> {
+    let Value::Object(mut call) = call else {
+        return Err((None, JsonRpcError::invalid_request("Invalid Request: expected an object")));
+    };
+
+    let id = match call.remove("id") {
+        None => None,
+        Some(id) => match serde_json::from_value::<JsonRpcId>(id) {
+            Ok(id) => Some(id),
+            Err(_) => return Err((None, JsonRpcError::invalid_request("Invalid Request: invalid id"))),
+        },
+    };
+    if call.get("jsonrpc").and_then(Value::as_str) != Some("2.0") {
+        return Err((id, JsonRpcError::invalid_request("Invalid Request: jsonrpc must be \"2.0\"")));
+    }
+    let Some(Value::String(method)) = call.remove("method") else {
+        return Err((id, JsonRpcError::invalid_request("Invalid Request: missing method")));
+    };
+    let params = match call.remove("params") {
+        None => Params::Positional(Vec::new()),
+        Some(Value::Array(params)) => Params::Positional(params),
+        Some(Value::Object(params)) => Params::Named(params),
+        Some(_) => return Err((id, JsonRpcError::invalid_params("Invalid params: expected an array or object"))),
+    };
+    Ok((id, method, params))
+}
+
+fn error_response(id: JsonRpcId, error: JsonRpcError) -> Value {
+    serde_json::to_value(JsonRpcResult::<Value>::Error(JsonRpcErrorResponse {
+        jsonrpc: "2.0".into(),
+        id,
+        error,
+    }))
+    .expect("serializable error response")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        http::{AuthMethod, HTTPBody, HTTPMethod},
+        provider::{JsonRpcProviderType, Provider},
+        target::{JsonRpcTarget, Target},
+    };
+    use serde_json::json;
+    use std::{
+        borrow::Cow,
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    #[derive(Debug, PartialEq)]
+    enum Calc {
+        Add(i64, i64),
+        Log(String),
+    }
+
+    impl Target for Calc {
+        fn base_url(&self) -> Cow<'_, str> {
+            Cow::Borrowed("http://calc.local")
+        }
+
+        fn method(&self) -> HTTPMethod {
+            HTTPMethod::POST
+        }
+
+        fn path(&self) -> String {
+            "/".into()
+        }
+
+        fn query(&self) -> HashMap<String, String> {
+            HashMap::default()
+        }
+
+        fn headers(&self) -> HashMap<String, String> {
+            HashMap::default()
+        }
+
+        fn authentication(&self) -> Option<AuthMethod> {
+            None
+        }
+
+        fn body(&self) -> Result<HTTPBody, Error> {
+            Ok(HTTPBody::default())
+        }
+    }
+
+    impl JsonRpcTarget for Calc {
+        fn method_name(&self) -> Cow<'static, str> {
+            match self {
+                Calc::Add(..) => Cow::Borrowed("add"),
+                Calc::Log(_) => Cow::Borrowed("log"),
+            }
+        }
+
+        fn params(&self) -> Vec<Value> {
+            match self {
+                Calc::Add(a, b) => vec![json!(a), json!(b)],
+                Calc::Log(message) => vec![json!(message)],
+            }
+        }
+
+        fn is_notification(&self) -> bool {
+            matches!(self, Calc::Log(_))
+        }
+    }
+
+    impl FromJsonRpc for Calc {
+        fn from_json_rpc(method: &str, params: Vec<Value>) -> Result<Self, JsonRpcError> {
+            match method {
+                "add" => Ok(Calc::Add(param(&params, 0)?, param(&params, 1)?)),
+                "log" => Ok(Calc::Log(param(&params, 0)?)),
+                _ => Err(JsonRpcError::method_not_found(method)),
+            }
+        }
+
+        fn from_json_rpc_named(method: &str, params: Map<String, Value>) -> Result<Self, JsonRpcError> {
+            match method {
+                "add" => Ok(Calc::Add(named_param(&params, "a")?, named_param(&params, "b")?)),
+                _ => Err(JsonRpcError::invalid_params("Invalid params: expected an array")),
+            }
+        }
+    }
+
+    fn router(logged: Arc<AtomicUsize>) -> JsonRpcRouter<Calc> {
+        JsonRpcRouter::new(move |target| {
+            let logged = logged.clone();
+            async move {
+                match target {
+                    Calc::Add(a, b) => Ok(json!(a + b)),
+                    Calc::Log(_) => {
+                        logged.fetch_add(1, Ordering::SeqCst);
+                        Ok(Value::Null)
+                    }
+                }
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_provider_against_router() {
+        let logged = Arc::new(AtomicUsize::new(0));
+        let provider = Provider::<Calc>::default().with_transport(router(logged.clone()));
+
+        let result: JsonRpcResult<i64> = provider.call(Calc::Add(2, 3)).await.unwrap();
+        assert!(matches!(result, JsonRpcResult::Value(response) if response.result == 5));
+
+        let results = provider
+            .batch::<i64>(vec![Calc::Add(1, 1), Calc::Log("hi".into()), Calc::Add(2, 2)])
+            .await
+            .unwrap();
+        let sums = results
+            .into_iter()
+            .map(|result| match result {
+                JsonRpcResult::Value(response) => response.result,
+                JsonRpcResult::Error(response) => panic!("{}", response.error),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(sums, vec![2, 4]);
+
+        provider.notify(Calc::Log("bye".into())).await.unwrap();
+        assert_eq!(logged.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_spec_errors() {
+        let router = router(Arc::default());
+
+        let response = router.handle(b"{not json").await.unwrap();
+        assert_eq!(response["error"]["code"], -32700);
+        assert_eq!(response["id"], Value::Null);
+
+        let response = router.handle(b"[]").await.unwrap();
+        assert_eq!(response["error"]["code"], -32600);
+
+        let response = router
+            .handle(br#"[1, {"jsonrpc":"2.0","id":"a","method":"nope"}, {"jsonrpc":"2.0","id":7,"method":"add","params":["x"]}]"#)
+            .await
+            .unwrap();
+        assert_eq!(response[0]["error"]["code"], -32600);
+        assert_eq!(response[1]["id"], "a");
+        assert_eq!(response[1]["error"]["code"], -32601);
+        assert_eq!(response[2]["id"], 7);
+        assert_eq!(response[2]["error"]["code"], -32602);
+
+        assert!(router.handle(br#"[{"jsonrpc":"2.0","method":"log","params":["x"]}]"#).await.is_none());
+        let response = router.handle_http(http::Request::new(Bytes::from_static(br#"{"jsonrpc":"2.0","method":"log","params":["x"]}"#))).await;
+        assert_eq!(response.status(), http::StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_named_params() {
+        let router = router(Arc::default());
+
+        let response = router
+            .handle(br#"[{"jsonrpc":"2.0","id":1,"method":"add","params":{"a":2,"b":5}}, {"jsonrpc":"2.0","id":2,"method":"log","params":{"message":"x"}}, {"jsonrpc":"2.0","id":3,"method":"add","params":3}]"#)
+            .await
+            .unwrap();
+        assert_eq!(response[0]["result"], 7);
+        assert_eq!(response[1]["error"]["code"], -32602);
+        assert_eq!(response[2]["error"]["code"], -32602);
+    }
+}
@@ -3,11 +3,29 @@
 //! # Key Features
 //!
 //! *   **Target Trait**: Define API endpoints by implementing `target::Target`.
+//! *   **Path Templates**: `path!` and `path::PathBuilder` percent-encode each path segment; dot-segments are rejected.
+//! *   **URL Building**: `url::build_url` joins base URL, path and query with slash normalization, failing with `Error::InvalidUrl`.
+//! *   **Query Parameters**: `query::QueryParams` keeps parameter order, percent-encodes query strings, and writes repeated, array and object values in a configurable `QueryStyle`.
+//! *   **Plain HTTP Requests**: `Target::to_http_request` converts a target into an `http::Request` for use with other HTTP stacks.
+//! *   **Server-side Routing**: `routing::FromHttpRequest` parses incoming requests back into the `Target` enum, with an axum extractor (via `axum` feature).
 //! *   **Provider Pattern**: Centralize request logic and client configuration with `provider::Provider`.
 //! *   **Flexible Authentication**: Use `http::AuthMethod` for Basic, Bearer, or custom closure-based authentication (e.g., `AuthMethod::header_api_key`).
 //! *   **Centralized Timeout**: Set a default timeout at the `Provider` level.
+//! *   **Pluggable Transports**: `Provider` sends requests through a `transport::Transport`, with `reqwest`, `reqwest-middleware` and in-memory implementations included.
+//! *   **Tower Integration**: `service::ProviderService` exposes a `Provider` as a `tower::Service`, and `with_service` sends requests through one (via `tower` feature).
+//! *   **Blocking Client**: `blocking::BlockingProvider` sends the same targets synchronously (via `blocking` feature).
+//! *   **Mock Server**: `testing::MockServer` answers requests from expectations keyed by targets (via `testing` feature).
+//! *   **Request Snapshots**: `assert_request_snapshot!` compares built requests with golden files (via `testing` feature).
+//! *   **Fault Injection**: `chaos::ChaosLayer` injects latency, errors and corrupted responses into a `Provider` (via `testing` feature).
 //! *   **Middleware Support**: Optional `reqwest-middleware` integration (via `middleware` feature).
 //! *   **JSON-RPC Support**: Optional helpers for JSON-RPC 2.0, including batching (via `jsonrpc` feature).
+//! *   **JSON-RPC Server**: `jsonrpc_router::JsonRpcRouter` dispatches incoming JSON-RPC requests back onto a `JsonRpcTarget`.
+//! *   **Circuit Breaking**: `circuit::CircuitBreaker` makes a `Provider` fail fast while a downstream is down (via `circuit` feature).
+//! *   **Failover**: `failover::FailoverProvider` balances requests across equivalent endpoints and skips unhealthy ones (via `failover` feature).
+//! *   **Hedged Requests**: `hedge::HedgePolicy` races a second copy of slow idempotent requests (via `hedge` feature).
+//! *   **Request Deduplication**: `dedup::DedupProvider` shares one network call between concurrent identical requests (via `dedup` feature).
+//! *   **Request Coalescing**: `coalesce::CoalescingProvider` merges concurrent JSON-RPC calls into batches (via `coalesce` feature).
+//! *   **Quorum Reads**: `quorum::QuorumProvider` only accepts a JSON-RPC result enough independent providers agree on.
 //!
 //! # Getting Started
 //!
@@ -22,8 +40,42 @@
 pub mod error;
 pub use error::Error;
 pub mod http;
+pub mod path;
 pub mod provider;
+pub mod query;
+pub mod routing;
 pub mod target;
+pub mod transport;
+pub mod url;
 
 #[cfg(feature = "jsonrpc")]
 pub mod jsonrpc;
+#[cfg(feature = "jsonrpc")]
+pub mod quorum;
+#[cfg(feature = "jsonrpc")]
+pub mod jsonrpc_router;
+
+#[cfg(feature = "circuit")]
+pub mod circuit;
+#[cfg(feature = "coalesce")]
+pub mod coalesce;
+#[cfg(feature = "dedup")]
+pub mod dedup;
+#[cfg(feature = "failover")]
+pub mod failover;
+#[cfg(feature = "hedge")]
+pub mod hedge;
+
+#[cfg(feature = "tower")]
+pub mod service;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+#[cfg(any(test, feature = "testing"))]
+pub mod chaos;
+#[cfg(any(test, feature = "testing"))]
+pub mod snapshot;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,186 @@
+//! Paths with safely encoded segments, so values like `a/b`, `?x` or `..` can't change the route.
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC, utf8_percent_encode};
+use std::fmt::Display;
+
+/// Everything but RFC 3986 unreserved characters is encoded, including `/`, `?`, `#` and `%`.
+const SEGMENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// Formats a path, percent-encoding each argument as one path segment.
+///
+/// Placeholders are those of `format!`: named ones filled from variables of the same name or
+/// `name = value` arguments, or positional `{}`. Every named placeholder must be passed as an
+/// argument, otherwise `format!` would capture the variable in scope unencoded; a missing one
+/// is a compile error. Arguments that are dot-segments (`.` or `..`) are kept as is, and
+/// `url::build_url` rejects them with `Error::InvalidUrl`.
+///
+/// ```rust,ignore
+/// fn path(&self) -> String {
+///     match self {
+///         GitHub::Repo { owner, repo } => path!("/repos/{owner}/{repo}", owner, repo),
+///         GitHub::User(name) => path!("/users/{}", name),
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! path {
+    ($template:literal $(,)?) => {{
+        const { $crate::path::check_template($template, &[]) };
+        format!($template)
+    }};
+    ($template:literal $(, $name:ident = $value:expr)+ $(,)?) => {{
+        const { $crate::path::check_template($template, &[$(stringify!($name)),+]) };
+        format!($template, $($name = $crate::path::encode_segment(&$value)),+)
+    }};
+    ($template:literal $(, $name:ident)+ $(,)?) => {{
+        const { $crate::path::check_template($template, &[$(stringify!($name)),+]) };
+        #[allow(named_arguments_used_positionally)]
+        let path = format!($template, $($name = $crate::path::encode_segment(&$name)),+);
+        path
+    }};
+    ($template:literal $(, $value:expr)+ $(,)?) => {{
+        const { $crate::path::check_template($template, &[]) };
+        format!($template, $($crate::path::encode_segment(&$value)),+)
+    }};
+}
+
+/// Checks that every named placeholder of a `path!` template is one of `names`, so none is
+/// filled by an implicitly captured, unencoded variable. Evaluated at compile time by `path!`.
+#[doc(hidden)]
+pub const fn check_template(template: &str, names: &[&str]) {
+    let bytes = template.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] != b'{' {
+            i += 1;
+            continue;
+        }
+        if i + 1 < bytes.len() && bytes[i + 1] == b'{' {
+            i += 2;
+            continue;
+        }
+        let start = i + 1;
+        let mut end = start;
+        while end < bytes.len() && bytes[end] != b'}' && bytes[end] != b':' {
+            end += 1;
+        }
+        let name = bytes.split_at(end).0.split_at(start).1;
+        let positional = name.is_empty() || name[0].is_ascii_digit();
+        if !positional && !contains(names, name) {
+            panic!("path! placeholder without a matching argument, pass it (`name` or `name = value`) so it is encoded");
+        }
+        i = end;
+    }
+}
+
+const fn contains(names: &[&str], name: &[u8]) -> bool {
+    let mut n = 0;
+    while n < names.len() {
+        let candidate = names[n].as_bytes();
+        if candidate.len() == name.len() {
+            let mut k = 0;
+            while k < name.len() && candidate[k] == name[k] {
+                k += 1;
+            }
+            if k == name.len() {
+                return true;
+            }
+        }
+        n += 1;
+    }
+    false
+}
+
+/// Percent-encodes `value` as a single path segment; see `path!`.
+pub fn encode_segment<V: Display + ?Sized>(value: &V) -> String {
+    utf8_percent_encode(&value.to_string(), SEGMENT).to_string()
+}
+
+/// Builds a path from literal parts and encoded segments, for paths assembled at runtime.
+///
+/// ```rust,ignore
+/// let mut path = PathBuilder::new().literal("/files");
+/// for part in ["docs", "a b.txt"] {
+///     path = path.segment(part);
+/// }
+/// assert_eq!(path.build(), "/files/docs/a%20b.txt");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PathBuilder {
+    path: String,
+}
+
+impl PathBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends `literal` unencoded, e.g. `/api/v1`.
+    pub fn literal(mut self, literal: &str) -> Self {
+        self.path.push_str(literal);
+        self
+    }
+
+    /// Appends `/` and `value` encoded as one segment.
+    pub fn segment<V: Display + ?Sized>(mut self, value: &V) -> Self {
+        self.path.push('/');
+        self.path.push_str(&encode_segment(value));
+        self
+    }
+
+    pub fn build(self) -> String {
+        self.path
+    }
+}
+
+/// Whether `path` contains a `.` or `..` segment, including percent-encoded ones (`%2e%2E`),
+/// which URL parsers resolve as well.
+pub(crate) fn has_dot_segment(path: &str) -> bool {
+    path.split('/').any(|segment| {
+        let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+        segment == "." || segment == ".."
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::Error, url::build_url};
+
+    #[test]
+    fn test_path_macro() {
+        let owner = "a/b";
+        let repo = "c?d#e";
+        assert_eq!(crate::path!("/repos/{owner}/{repo}", owner, repo), "/repos/a%2Fb/c%3Fd%23e");
+        assert_eq!(crate::path!("/repos/{owner}/issues", owner = "é 100%"), "/repos/%C3%A9%20100%25/issues");
+        assert_eq!(crate::path!("/users/{}/{}", 42, "x~y.z"), "/users/42/x~y.z");
+        assert_eq!(crate::path!("/health"), "/health");
+        assert_eq!(crate::path!("/{{literal}}"), "/{literal}");
+    }
+
+    #[test]
+    fn test_placeholders_need_arguments() {
+        check_template("/repos/{owner}/{repo:?}/{}/{0}/{{x}}", &["owner", "repo"]);
+        for (template, names) in [("/repos/{owner}/{repo}", &["owner"][..]), ("/users/{name}", &[]), ("/{}/{id}", &[])] {
+            assert!(std::panic::catch_unwind(|| check_template(template, names)).is_err(), "{}", template);
+        }
+    }
+
+    #[test]
+    fn test_dot_segments_are_rejected() {
+        let name = "..";
+        let path = crate::path!("/users/{name}/profile", name);
+        let err = build_url("https://api.example", &path, "").unwrap_err();
+        assert!(matches!(err, Error::InvalidUrl { .. }), "{}", err);
+
+        for path in ["/a/./b", "/a/%2E%2e", "/.%2E/admin"] {
+            assert!(has_dot_segment(path), "{}", path);
+        }
+        assert!(!has_dot_segment("/a/..b/c.d/.well-known"));
+    }
+
+    #[test]
+    fn test_path_builder() {
+        let path = PathBuilder::new().literal("/files").segment("docs").segment("a b/c.txt").build();
+        assert_eq!(path, "/files/docs/a%20b%2Fc.txt");
+    }
+}
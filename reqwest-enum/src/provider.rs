@@ -1,25 +1,138 @@
-use std::{time::Duration, future::Future};
+use std::{time::Duration, future::Future, sync::{Arc, OnceLock}};
+#[cfg(feature = "jsonrpc")]
+use std::{
+    collections::{HashMap, VecDeque},
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 use serde::de::DeserializeOwned;
 use crate::{
-    http::{AuthMethod, HTTPBody, HTTPResponse},
+    http::{AuthMethod, HTTPResponse},
     error::Error,
+    query::QueryStyle,
     target::Target,
+    transport::{DefaultTransport, Transport, execute_reqwest, from_reqwest_request},
+    url::build_url,
 };
 
 #[cfg(feature = "jsonrpc")]
 use crate::{
-    jsonrpc::{JsonRpcError, JsonRpcRequest, JsonRpcResult},
+    http::HTTPBody,
+    jsonrpc::{
+        BatchLimits, ChunkOutcome, IdGenerator, JsonRpcError, JsonRpcErrorResponse, JsonRpcId, JsonRpcRequest,
+        JsonRpcResult, SequentialIdGenerator, is_batch_too_large,
+    },
     target::JsonRpcTarget,
 };
 #[cfg(feature = "jsonrpc")]
-use futures::future::join_all;
+use futures::stream::{self, StreamExt};
 
-#[cfg(not(feature = "middleware"))]
-pub type ProviderRequestBuilder = reqwest::RequestBuilder;
-#[cfg(feature = "middleware")]
-pub type ProviderRequestBuilder = reqwest_middleware::RequestBuilder;
+#[cfg(feature = "circuit")]
+use crate::circuit::CircuitBreaker;
+#[cfg(feature = "hedge")]
+use crate::hedge::HedgePolicy;
 #[cfg(feature = "middleware")]
-use reqwest_middleware::{ClientBuilder as MiddlewareClientBuilder, ClientWithMiddleware};
+use crate::transport::MiddlewareTransport;
+use crate::transport::ReqwestTransport;
+use reqwest::{
+    Version,
+    header::{HeaderMap, HeaderName, HeaderValue},
+};
+use bytes::Bytes;
+use serde::Serialize;
+use std::fmt::Display;
+
+/// Request builder passed to `RequestBuilderFn` and `AuthMethod::Custom` closures.
+///
+/// It is the same type whether or not the `middleware` feature is enabled; extensions added with
+/// `with_extension` are handed to `reqwest-middleware` middleware by `MiddlewareTransport`.
+#[derive(Debug)]
+pub struct ProviderRequestBuilder {
+    inner: reqwest::RequestBuilder,
+    extensions: http::Extensions,
+}
+
+impl ProviderRequestBuilder {
+    fn new(inner: reqwest::RequestBuilder) -> Self {
+        Self {
+            inner,
+            extensions: http::Extensions::new(),
+        }
+    }
+
+    pub fn header<K, V>(self, key: K, value: V) -> Self
+    where
+        HeaderName: TryFrom<K>,
+        <HeaderName as TryFrom<K>>::Error: Into<http::Error>,
+        HeaderValue: TryFrom<V>,
+        <HeaderValue as TryFrom<V>>::Error: Into<http::Error>,
+    {
+        self.map(|inner| inner.header(key, value))
+    }
+
+    pub fn headers(self, headers: HeaderMap) -> Self {
+        self.map(|inner| inner.headers(headers))
+    }
+
+    pub fn basic_auth<U: Display, P: Display>(self, username: U, password: Option<P>) -> Self {
+        self.map(|inner| inner.basic_auth(username, password))
+    }
+
+    pub fn bearer_auth<B: Display>(self, token: B) -> Self {
+        self.map(|inner| inner.bearer_auth(token))
+    }
+
+    pub fn body<B: Into<reqwest::Body>>(self, body: B) -> Self {
+        self.map(|inner| inner.body(body))
+    }
+
+    pub fn query<Q: Serialize + ?Sized>(self, query: &Q) -> Self {
+        self.map(|inner| inner.query(query))
+    }
+
+    pub fn json<J: Serialize + ?Sized>(self, json: &J) -> Self {
+        self.map(|inner| inner.json(json))
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.map(|inner| inner.timeout(timeout))
+    }
+
+    pub fn version(self, version: Version) -> Self {
+        self.map(|inner| inner.version(version))
+    }
+
+    /// Attaches a typed extension to the request, visible to custom transports and middleware.
+    pub fn with_extension<E: Clone + Send + Sync + 'static>(mut self, extension: E) -> Self {
+        self.extensions.insert(extension);
+        self
+    }
+
+    /// `None` if the body is a stream that can't be cloned.
+    pub fn try_clone(&self) -> Option<Self> {
+        Some(Self {
+            inner: self.inner.try_clone()?,
+            extensions: self.extensions.clone(),
+        })
+    }
+
+    pub fn build(self) -> Result<reqwest::Request, Error> {
+        let request = self.inner.build()?;
+        if self.extensions.is_empty() {
+            return Ok(request);
+        }
+        let mut request = http::Request::<reqwest::Body>::try_from(request)?;
+        request.extensions_mut().extend(self.extensions);
+        Ok(reqwest::Request::try_from(request)?)
+    }
+
+    fn map(self, f: impl FnOnce(reqwest::RequestBuilder) -> reqwest::RequestBuilder) -> Self {
+        Self {
+            inner: f(self.inner),
+            extensions: self.extensions,
+        }
+    }
+}
 
 // Base trait for providers, defining the core request method.
 pub trait ProviderType<T: Target>: Send {
@@ -38,17 +151,46 @@ pub trait JsonProviderType<T: Target>: ProviderType<T> {
 
 #[cfg(feature = "jsonrpc")]
 pub trait JsonRpcProviderType<T: Target>: ProviderType<T> {
-    /// batch isomorphic JSON-RPC requests
+    /// send a single JSON-RPC request, using an id from the provider's `IdGenerator`
+    fn call<U: DeserializeOwned>(
+        &self,
+        target: T,
+    ) -> impl Future<Output = Result<JsonRpcResult<U>, JsonRpcError>>;
+
+    /// send a single JSON-RPC notification (no id), without waiting for a response body
+    fn notify(&self, target: T) -> impl Future<Output = Result<(), JsonRpcError>>;
+
+    /// batch isomorphic JSON-RPC requests, results only contain entries for non-notification targets
     fn batch<U: DeserializeOwned>(
         &self,
         targets: Vec<T>,
     ) -> impl Future<Output = Result<Vec<JsonRpcResult<U>>, JsonRpcError>>;
 
+    /// batch in chunks of `chunk_size`, at most `with_batch_concurrency` chunks in flight,
+    /// failing if any chunk fails
     fn batch_chunk_by<U: DeserializeOwned>(
         &self,
         targets: Vec<T>,
         chunk_size: usize,
     ) -> impl Future<Output = Result<Vec<JsonRpcResult<U>>, JsonRpcError>>;
+
+    /// like `batch_chunk_by`, but returns every chunk's outcome (in input order) so successful
+    /// results are kept and failed chunks can be retried; with `fail_fast`, the first failure
+    /// cancels in-flight and pending chunks
+    fn batch_chunk_outcomes<U: DeserializeOwned>(
+        &self,
+        targets: Vec<T>,
+        chunk_size: usize,
+        fail_fast: bool,
+    ) -> impl Future<Output = Result<Vec<ChunkOutcome<T, U>>, JsonRpcError>>;
+
+    /// batch split automatically by the provider's `BatchLimits` (item count and payload size);
+    /// batches rejected as too large are halved and retried, and the largest smaller size that
+    /// then succeeds is remembered for later calls. Results are returned in input order.
+    fn batch_auto<U: DeserializeOwned>(
+        &self,
+        targets: Vec<T>,
+    ) -> impl Future<Output = Result<Vec<JsonRpcResult<U>>, JsonRpcError>>;
 }
 
 pub type EndpointFn<T> = fn(target: &T) -> String;
@@ -56,44 +198,79 @@ pub type RequestBuilderFn<T> =
     Box<dyn Fn(&ProviderRequestBuilder, &T) -> ProviderRequestBuilder + Send + Sync>;
 
 /// Generic provider for HTTP requests to a `Target`. Handles construction, auth, and execution.
-pub struct Provider<T: Target> {
+/// Requests are sent through the `Transport` `X`, see `with_transport`.
+pub struct Provider<T: Target, X: Transport = DefaultTransport> {
     /// endpoint closure to customize the endpoint (url / path)
     endpoint_fn: Option<EndpointFn<T>>,
     request_fn: Option<RequestBuilderFn<T>>,
     /// An optional default timeout for all requests made by this provider.
     /// If set, this timeout is applied to each request unless overridden by more specific timeout logic.
     timeout: Option<Duration>,
-    #[cfg(not(feature = "middleware"))]
+    /// Overrides `Target::base_url` for every request, see `with_base_url`.
+    base_url: Option<String>,
+    /// How list and object query values are written unless the target sets its own style.
+    query_style: QueryStyle,
+    #[cfg(feature = "circuit")]
+    circuit_breaker: Option<CircuitBreaker<T>>,
+    #[cfg(feature = "hedge")]
+    hedging: Option<HedgePolicy<T>>,
+    /// Id generator shared by every JSON-RPC call made through this provider.
+    #[cfg(feature = "jsonrpc")]
+    id_generator: Arc<dyn IdGenerator>,
+    /// Maximum number of batch chunks in flight at once, unlimited if `None`.
+    #[cfg(feature = "jsonrpc")]
+    batch_concurrency: Option<usize>,
+    #[cfg(feature = "jsonrpc")]
+    batch_limits: BatchLimits,
+    /// Largest batch size the server accepted after rejecting a bigger one, learned by `batch_auto`.
+    #[cfg(feature = "jsonrpc")]
+    learned_batch_items: AtomicUsize,
+    /// Client used to build requests, which are then sent through `transport`.
     client: reqwest::Client,
-    #[cfg(feature = "middleware")]
-    client: ClientWithMiddleware,
+    transport: Arc<X>,
 }
 
-impl<T: Target> std::fmt::Debug for Provider<T> {
+impl<T: Target, X: Transport> std::fmt::Debug for Provider<T, X> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Provider")
+        let mut debug = f.debug_struct("Provider");
+        debug
             .field("endpoint_fn", &self.endpoint_fn.map(|_| "<function>")) // Print placeholder for fn pointer
             .field("request_fn", &self.request_fn.as_ref().map(|_| "<function>")) // Print placeholder for Box<dyn Fn>
             .field("timeout", &self.timeout)
-            .field("client", &self.client) // reqwest::Client and reqwest_middleware::ClientWithMiddleware implement Debug
+            .field("base_url", &self.base_url)
+            .field("query_style", &self.query_style);
+        #[cfg(feature = "circuit")]
+        debug.field("circuit_breaker", &self.circuit_breaker);
+        #[cfg(feature = "hedge")]
+        debug.field("hedging", &self.hedging);
+        #[cfg(feature = "jsonrpc")]
+        debug
+            .field("id_generator", &"<generator>")
+            .field("batch_concurrency", &self.batch_concurrency)
+            .field("batch_limits", &self.batch_limits);
+        debug
+            .field("client", &self.client)
+            .field("transport", &std::any::type_name::<X>())
             .finish()
     }
 }
 
-impl<T> ProviderType<T> for Provider<T>
+impl<T, X> ProviderType<T> for Provider<T, X>
 where
     T: Target + Send,
+    X: Transport,
 {
     /// Builds and executes a request to `Target`, returning raw `HTTPResponse`.
     async fn request(&self, target: T) -> Result<HTTPResponse, Error> {
         let req = self.request_builder(&target)?.build()?;
-        self.client.execute(req).await.map_err(Error::from)
+        self.send(&target, req).await
     }
 }
 
-impl<T> JsonProviderType<T> for Provider<T>
+impl<T, X> JsonProviderType<T> for Provider<T, X>
 where
     T: Target + Send,
+    X: Transport,
 {
     async fn request_json<U: DeserializeOwned>(&self, target: T) -> Result<U, Error> {
         let response = self.request(target).await?;
@@ -109,43 +286,44 @@ where
 }
 
 #[cfg(feature = "jsonrpc")]
-impl<T> JsonRpcProviderType<T> for Provider<T>
+impl<T, X> JsonRpcProviderType<T> for Provider<T, X>
 where
     T: JsonRpcTarget + Send,
+    X: Transport,
 {
+    async fn call<U: DeserializeOwned>(&self, target: T) -> Result<JsonRpcResult<U>, JsonRpcError> {
+        self.call_ref(&target).await
+    }
+
+    async fn notify(&self, target: T) -> Result<(), JsonRpcError> {
+        let notification = JsonRpcRequest::notification(target.method_name(), target.params());
+        #[cfg(feature = "tracing")]
+        tracing::debug!(method = %notification.method, "sending JSON-RPC notification");
+
+        let body = HTTPBody::from(&notification).map_err(|e| JsonRpcError::parse_error(format!("Failed to serialize notification: {}", e)))?;
+        let final_request = self
+            .request_builder(&target)?
+            .body(body.inner)
+            .build()
+            .map_err(|e| JsonRpcError::internal_error(format!("Failed to build notification: {}", e)))?;
+
+        self.send(&target, final_request)
+            .await
+            .map_err(|e| JsonRpcError::internal_error(format!("Notification execution failed: {}", e)))?
+            .error_for_status()?;
+        Ok(())
+    }
+
     async fn batch<U: DeserializeOwned>(
         &self,
         targets: Vec<T>,
     ) -> Result<Vec<JsonRpcResult<U>>, JsonRpcError> {
         if targets.is_empty() {
-            return Err(JsonRpcError {
-                code: -32600,
-                message: "Invalid Request".into(),
-            });
-        }
-
-        let representative_target = &targets[0];
-
-        let mut builder = self.request_builder(representative_target)?;
-
-        let mut rpc_payload = Vec::new();
-        for (k, individual_target) in targets.iter().enumerate() {
-            let req = JsonRpcRequest::new(individual_target.method_name(), individual_target.params(), (k + 1) as u64);
-            rpc_payload.push(req);
+            return Err(JsonRpcError::invalid_request("Invalid Request"));
         }
-        let body = HTTPBody::from_array(&rpc_payload).map_err(|e| JsonRpcError { code: -32700, message: format!("Failed to serialize batch request: {}", e) })?;
 
-        builder = builder.body(body.inner);
-
-        // Build the final reqwest::Request
-        let final_request = builder.build().map_err(|e| JsonRpcError { code: -32603, message: format!("Failed to build batch request: {}", e) })?;
-
-        // Execute the request using self.client
-        let response = self.client.execute(final_request).await.map_err(|e| JsonRpcError { code: -32603, message: format!("Batch request execution failed: {}", e) })?;
-        
-        // Deserialize the response
-        let response_body = response.json::<Vec<JsonRpcResult<U>>>().await.map_err(|e| JsonRpcError { code: -32700, message: format!("Failed to parse batch JSON response: {}", e) })?;
-        Ok(response_body)
+        let rpc_payload = self.rpc_requests(&targets);
+        self.send_rpc_batch(&targets[0], &rpc_payload).await
     }
 
     async fn batch_chunk_by<U: DeserializeOwned>(
@@ -153,48 +331,13 @@ where
         targets: Vec<T>,
         chunk_size: usize,
     ) -> Result<Vec<JsonRpcResult<U>>, JsonRpcError> {
-        if targets.is_empty() || chunk_size == 0 {
-            return Err(JsonRpcError {
-                code: -32600,
-                message: "Invalid Request".into(),
-            });
-        }
-
-        let chunk_targets = targets.chunks(chunk_size).collect::<Vec<_>>();
-        let mut rpc_requests = Vec::<ProviderRequestBuilder>::new();
-
-        for (chunk_idx, chunk) in chunk_targets.into_iter().enumerate() {
-            let target = &chunk[0];
-            let mut request = self.request_builder(target);
-            let mut requests = Vec::<JsonRpcRequest>::new();
-            for (k, v) in chunk.iter().enumerate() {
-                let request = JsonRpcRequest::new(
-                    v.method_name(),
-                    v.params(),
-                    (chunk_idx * chunk_size + k + 1) as u64,
-                );
-                requests.push(request);
-            }
-
-            let http_body = HTTPBody::from_array(&requests).map_err(|e| JsonRpcError { code: -32700, message: format!("Failed to serialize batch chunk: {}", e) })?;
-            request = Ok(request?.body(http_body.inner));
-            rpc_requests.push(request?);
-        }
-        let bodies = join_all(rpc_requests.into_iter().map(|request| async move {
-            #[cfg(feature = "middleware")]
-            let response = request.send().await.map_err(crate::Error::ReqwestMiddleware)?;
-            #[cfg(not(feature = "middleware"))]
-            let response = request.send().await?;
-            let body = response.json::<Vec<JsonRpcResult<U>>>().await?;
-            Ok(body)
-        }))
-        .await;
+        let outcomes = self.batch_chunk_outcomes::<U>(targets, chunk_size, false).await?;
 
         let mut results = Vec::<JsonRpcResult<U>>::new();
         let mut error: Option<JsonRpcError> = None;
 
-        for result in bodies {
-            match result {
+        for outcome in outcomes {
+            match outcome.result {
                 Ok(body) => {
                     results.extend(body);
                 }
@@ -208,6 +351,269 @@ where
         }
         Ok(results)
     }
+
+    async fn batch_chunk_outcomes<U: DeserializeOwned>(
+        &self,
+        targets: Vec<T>,
+        chunk_size: usize,
+        fail_fast: bool,
+    ) -> Result<Vec<ChunkOutcome<T, U>>, JsonRpcError> {
+        if targets.is_empty() || chunk_size == 0 {
+            return Err(JsonRpcError::invalid_request("Invalid Request"));
+        }
+
+        let mut chunks = Vec::<Vec<T>>::new();
+        let mut remaining = targets.into_iter();
+        loop {
+            let chunk = remaining.by_ref().take(chunk_size).collect::<Vec<_>>();
+            if chunk.is_empty() {
+                break;
+            }
+            chunks.push(chunk);
+        }
+        let payloads = chunks.iter().map(|chunk| self.rpc_requests(chunk)).collect::<Vec<_>>();
+
+        let limit = self.batch_concurrency.unwrap_or(chunks.len()).max(1);
+        let mut slots = (0..chunks.len()).map(|_| None).collect::<Vec<_>>();
+        {
+            let mut in_flight = stream::iter(chunks.iter().zip(payloads.iter()).enumerate())
+                .map(|(idx, (chunk, requests))| async move {
+                    (idx, self.send_rpc_batch::<U>(&chunk[0], requests).await)
+                })
+                .buffer_unordered(limit);
+
+            while let Some((idx, result)) = in_flight.next().await {
+                let failed = result.is_err();
+                slots[idx] = Some(result);
+                if failed && fail_fast {
+                    // dropping the stream cancels chunks still in flight
+                    break;
+                }
+            }
+        }
+
+        let mut offset = 0;
+        let outcomes = chunks
+            .into_iter()
+            .zip(slots)
+            .map(|(targets, slot)| {
+                let outcome = ChunkOutcome {
+                    offset,
+                    result: slot.unwrap_or_else(|| {
+                        Err(JsonRpcError::internal_error("Chunk cancelled after an earlier chunk failed"))
+                    }),
+                    targets,
+                };
+                offset += outcome.targets.len();
+                outcome
+            })
+            .collect();
+        Ok(outcomes)
+    }
+
+    async fn batch_auto<U: DeserializeOwned>(
+        &self,
+        targets: Vec<T>,
+    ) -> Result<Vec<JsonRpcResult<U>>, JsonRpcError> {
+        if targets.is_empty() {
+            return Err(JsonRpcError::invalid_request("Invalid Request"));
+        }
+
+        let requests = self.rpc_requests(&targets);
+        let sizes = requests
+            .iter()
+            .map(|request| serde_json::to_vec(request).map(|bytes| bytes.len()))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| JsonRpcError::parse_error(format!("Failed to serialize batch request: {}", e)))?;
+
+        let limit = self.batch_concurrency.unwrap_or(usize::MAX).max(1);
+        let mut pending = self.batch_limits.split(&sizes, self.learned_batch_items.load(Ordering::Relaxed));
+        // results of each sent range, matched against that range's own requests
+        let mut answered = Vec::<(usize, Vec<JsonRpcResult<U>>)>::new();
+        // smallest batch rejected as too large, and the batch sizes answered successfully
+        let mut smallest_rejected = usize::MAX;
+        let mut accepted = Vec::new();
+
+        while !pending.is_empty() {
+            let mut sent = stream::iter(pending.drain(..))
+                .map(|range: Range<usize>| {
+                    let (target, requests) = (&targets[range.start], &requests[range.clone()]);
+                    async move { (range, self.execute_rpc_batch(target, requests).await) }
+                })
+                .buffer_unordered(limit);
+
+            let mut retry = Vec::new();
+            while let Some((range, result)) = sent.next().await {
+                let (status, body) = result?;
+                if range.len() > 1 && is_batch_too_large(status, &body) {
+                    let half = range.len().div_ceil(2);
+                    smallest_rejected = smallest_rejected.min(range.len());
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(items = range.len(), "JSON-RPC batch too large, halving");
+                    retry.push(range.start..range.start + half);
+                    retry.push(range.start + half..range.end);
+                    continue;
+                }
+                let results = parse_batch_response::<U>(status, &body, &requests[range.clone()])?;
+                answered.push((range.start, match_batch_results(&requests[range.clone()], results)));
+                accepted.push(range.len());
+            }
+            drop(sent);
+            pending = retry;
+        }
+
+        // only remember a size the server has actually accepted
+        if smallest_rejected != usize::MAX
+            && let Some(confirmed) = accepted.into_iter().filter(|len| *len < smallest_rejected).max()
+        {
+            self.learned_batch_items.fetch_min(confirmed, Ordering::Relaxed);
+        }
+
+        // the ranges cover the input without overlapping, so their start restores the input order
+        answered.sort_by_key(|(start, _)| *start);
+        let results = answered.into_iter().flat_map(|(_, results)| results).collect();
+        Ok(results)
+    }
+}
+
+#[cfg(feature = "jsonrpc")]
+impl<T, X> Provider<T, X>
+where
+    T: JsonRpcTarget,
+    X: Transport,
+{
+    /// Sets the `IdGenerator` used for all JSON-RPC requests made by this provider.
+    /// Defaults to a `SequentialIdGenerator`.
+    pub fn with_id_generator<G: IdGenerator + 'static>(mut self, id_generator: G) -> Self {
+        self.id_generator = Arc::new(id_generator);
+        self
+    }
+
+    /// Limits how many chunks `batch_chunk_by`, `batch_chunk_outcomes` and `batch_auto` send concurrently.
+    pub fn with_batch_concurrency(mut self, limit: usize) -> Self {
+        self.batch_concurrency = Some(limit);
+        self
+    }
+
+    /// Sets the item count and payload size limits used by `batch_auto`.
+    pub fn with_batch_limits(mut self, limits: BatchLimits) -> Self {
+        self.batch_limits = limits;
+        self
+    }
+
+    /// `call` without consuming the target, so it can be retried or fanned out.
+    pub(crate) async fn call_ref<U: DeserializeOwned>(&self, target: &T) -> Result<JsonRpcResult<U>, JsonRpcError> {
+        let request = self.rpc_request(target);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(id = request.id.as_ref().map(tracing::field::display), method = %request.method, "sending JSON-RPC request");
+
+        let body = HTTPBody::from(&request).map_err(|e| JsonRpcError::parse_error(format!("Failed to serialize request: {}", e)))?;
+        let final_request = self
+            .request_builder(target)?
+            .body(body.inner)
+            .build()
+            .map_err(|e| JsonRpcError::internal_error(format!("Failed to build request: {}", e)))?;
+
+        let response = self.send(target, final_request).await.map_err(|e| JsonRpcError::internal_error(format!("Request execution failed: {}", e)))?;
+        let result = response.json::<JsonRpcResult<U>>().await.map_err(|e| JsonRpcError::parse_error(format!("Failed to parse JSON response: {}", e)))?;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(id = %result.id(), "received JSON-RPC response");
+        Ok(result)
+    }
+
+    /// Builds the `JsonRpcRequest` for a single call, with a fresh id from the provider's generator.
+    pub(crate) fn rpc_request(&self, target: &T) -> JsonRpcRequest {
+        JsonRpcRequest::with_id(target.method_name(), target.params(), self.id_generator.next_id())
+    }
+
+    /// Builds one `JsonRpcRequest` per target, each with a fresh id from the provider's generator
+    /// unless the target is a notification.
+    pub(crate) fn rpc_requests(&self, targets: &[T]) -> Vec<JsonRpcRequest> {
+        targets
+            .iter()
+            .map(|target| {
+                if target.is_notification() {
+                    JsonRpcRequest::notification(target.method_name(), target.params())
+                } else {
+                    JsonRpcRequest::with_id(target.method_name(), target.params(), self.id_generator.next_id())
+                }
+            })
+            .collect()
+    }
+
+    /// Sends `requests` as one JSON-RPC batch, using `target` for the URL, headers and auth.
+    pub(crate) async fn send_rpc_batch<U: DeserializeOwned>(
+        &self,
+        target: &T,
+        requests: &[JsonRpcRequest],
+    ) -> Result<Vec<JsonRpcResult<U>>, JsonRpcError> {
+        let (status, body) = self.execute_rpc_batch(target, requests).await?;
+        parse_batch_response(status, &body, requests)
+    }
+
+    /// Executes a JSON-RPC batch and returns the raw HTTP status and response body.
+    async fn execute_rpc_batch(&self, target: &T, requests: &[JsonRpcRequest]) -> Result<(u16, Vec<u8>), JsonRpcError> {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            ids = ?requests.iter().filter_map(|r| r.id.as_ref().map(ToString::to_string)).collect::<Vec<_>>(),
+            "sending JSON-RPC batch"
+        );
+        let body = HTTPBody::from_array(requests).map_err(|e| JsonRpcError::parse_error(format!("Failed to serialize batch request: {}", e)))?;
+
+        // Build the final reqwest::Request
+        let final_request = self
+            .request_builder(target)?
+            .body(body.inner)
+            .build()
+            .map_err(|e| JsonRpcError::internal_error(format!("Failed to build batch request: {}", e)))?;
+
+        // Execute the request through the provider's circuit breaker and hedging policy, if any
+        let response = self.send(target, final_request).await.map_err(|e| JsonRpcError::internal_error(format!("Batch request execution failed: {}", e)))?;
+        let status = response.status().as_u16();
+        let body = response.bytes().await?;
+        Ok((status, body.to_vec()))
+    }
+}
+
+/// Deserializes a batch response body; a batch of only notifications gets no response body at all.
+#[cfg(feature = "jsonrpc")]
+pub(crate) fn parse_batch_response<U: DeserializeOwned>(
+    status: u16,
+    body: &[u8],
+    requests: &[JsonRpcRequest],
+) -> Result<Vec<JsonRpcResult<U>>, JsonRpcError> {
+    if requests.iter().all(JsonRpcRequest::is_notification) {
+        if status >= 400 {
+            return Err(JsonRpcError::internal_error(format!("Batch request failed with HTTP status {}", status)));
+        }
+        return Ok(Vec::new());
+    }
+    serde_json::from_slice::<Vec<JsonRpcResult<U>>>(body).map_err(|e| JsonRpcError::parse_error(format!("Failed to parse batch JSON response: {}", e)))
+}
+
+/// Orders the `results` of a batch like its `requests`, one per call expecting a result. Responses
+/// are matched by id; requests sharing an id (from an `IdGenerator` that repeats ids) take the
+/// responses with that id in the order they were answered. A call without a response gets an
+/// internal error.
+#[cfg(feature = "jsonrpc")]
+pub(crate) fn match_batch_results<U>(requests: &[JsonRpcRequest], results: Vec<JsonRpcResult<U>>) -> Vec<JsonRpcResult<U>> {
+    let mut by_id = HashMap::<JsonRpcId, VecDeque<_>>::new();
+    for result in results {
+        by_id.entry(result.id().clone()).or_default().push_back(result);
+    }
+    requests
+        .iter()
+        .filter_map(|request| request.id.as_ref())
+        .map(|id| {
+            by_id.get_mut(id).and_then(VecDeque::pop_front).unwrap_or_else(|| {
+                JsonRpcResult::Error(JsonRpcErrorResponse {
+                    jsonrpc: "2.0".into(),
+                    id: id.clone(),
+                    error: JsonRpcError::internal_error("Missing response for request"),
+                })
+            })
+        })
+        .collect()
 }
 
 impl<T> Provider<T>
@@ -220,21 +626,30 @@ where
         request_fn: Option<RequestBuilderFn<T>>,
         timeout: Option<Duration>,
     ) -> Self {
-        #[cfg(not(feature = "middleware"))]
         let client = reqwest::Client::new();
-        #[cfg(feature = "middleware")]
-        let client = {
-            MiddlewareClientBuilder::new(reqwest::Client::new()).build()
-        };
         Self {
+            transport: Arc::new(DefaultTransport::new(client.clone())),
             client,
             endpoint_fn,
             request_fn,
             timeout,
+            base_url: None,
+            query_style: QueryStyle::default(),
+            #[cfg(feature = "circuit")]
+            circuit_breaker: None,
+            #[cfg(feature = "hedge")]
+            hedging: None,
+            #[cfg(feature = "jsonrpc")]
+            id_generator: Arc::new(SequentialIdGenerator::default()),
+            #[cfg(feature = "jsonrpc")]
+            batch_concurrency: None,
+            #[cfg(feature = "jsonrpc")]
+            batch_limits: BatchLimits::default(),
+            #[cfg(feature = "jsonrpc")]
+            learned_batch_items: AtomicUsize::new(usize::MAX),
         }
     }
 
-    #[cfg(not(feature = "middleware"))]
     pub fn with_client(
         client: reqwest::Client,
         endpoint_fn: Option<EndpointFn<T>>,
@@ -243,58 +658,152 @@ where
         Self {
             endpoint_fn,
             request_fn,
+            transport: Arc::new(ReqwestTransport::new(client.clone())),
             client,
             timeout: None,
+            base_url: None,
+            query_style: QueryStyle::default(),
+            #[cfg(feature = "circuit")]
+            circuit_breaker: None,
+            #[cfg(feature = "hedge")]
+            hedging: None,
+            #[cfg(feature = "jsonrpc")]
+            id_generator: Arc::new(SequentialIdGenerator::default()),
+            #[cfg(feature = "jsonrpc")]
+            batch_concurrency: None,
+            #[cfg(feature = "jsonrpc")]
+            batch_limits: BatchLimits::default(),
+            #[cfg(feature = "jsonrpc")]
+            learned_batch_items: AtomicUsize::new(usize::MAX),
         }
     }
 
+    /// Sends requests through a `reqwest-middleware` client, so its middleware stack runs for
+    /// every request; requests are built the same way as with `with_client`.
     #[cfg(feature = "middleware")]
-    pub fn with_client(
-        client: ClientWithMiddleware,
+    pub fn with_middleware_client(
+        client: reqwest_middleware::ClientWithMiddleware,
         endpoint_fn: Option<EndpointFn<T>>,
         request_fn: Option<RequestBuilderFn<T>>,
-    ) -> Self {
-        Self {
-            endpoint_fn,
-            request_fn,
-            client,
-            timeout: None,
+    ) -> Provider<T, MiddlewareTransport> {
+        Self::with_client(reqwest::Client::new(), endpoint_fn, request_fn)
+            .with_transport(MiddlewareTransport::new(client))
+    }
+}
+
+impl<T, X> Provider<T, X>
+where
+    T: Target,
+    X: Transport,
+{
+    /// Sends requests through `transport` instead of the default `reqwest` client, e.g. an
+    /// `InMemoryTransport` in tests. Requests are still built the same way, including `request_fn`.
+    pub fn with_transport<Y: Transport>(self, transport: Y) -> Provider<T, Y> {
+        self.map_transport(|_| transport)
+    }
+
+    /// Replaces the transport with one built from the current one, e.g. a wrapper around it.
+    pub(crate) fn map_transport<Y: Transport>(self, f: impl FnOnce(Arc<X>) -> Y) -> Provider<T, Y> {
+        Provider {
+            endpoint_fn: self.endpoint_fn,
+            request_fn: self.request_fn,
+            timeout: self.timeout,
+            base_url: self.base_url,
+            query_style: self.query_style,
+            #[cfg(feature = "circuit")]
+            circuit_breaker: self.circuit_breaker,
+            #[cfg(feature = "hedge")]
+            hedging: self.hedging,
+            #[cfg(feature = "jsonrpc")]
+            id_generator: self.id_generator,
+            #[cfg(feature = "jsonrpc")]
+            batch_concurrency: self.batch_concurrency,
+            #[cfg(feature = "jsonrpc")]
+            batch_limits: self.batch_limits,
+            #[cfg(feature = "jsonrpc")]
+            learned_batch_items: self.learned_batch_items,
+            client: self.client,
+            transport: Arc::new(f(self.transport)),
         }
     }
 
-    pub fn request_url(&self, target: &T) -> String {
-        let mut url = format!("{}{}", target.base_url(), target.path());
+    /// Sends every request to `base_url` instead of `Target::base_url`, keeping each target's path.
+    /// Useful to point the same targets at several equivalent hosts; an `EndpointFn` still takes precedence.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// The URL requests for `target` are sent to, including the query string; without an
+    /// `EndpointFn`, base URL override or query style it equals `Target::absolute_url`.
+    /// An `EndpointFn` URL is used as is, without the target's path.
+    pub fn request_url(&self, target: &T) -> Result<reqwest::Url, Error> {
+        let query = target.query_params().encode(self.query_style);
         if let Some(func) = &self.endpoint_fn {
-            url = func(target);
+            return build_url(&func(target), "", &query);
+        }
+        match &self.base_url {
+            Some(base_url) => build_url(base_url, &target.path(), &query),
+            None => build_url(&target.base_url(), &target.path(), &query),
         }
-        url
     }
 
-    /// Constructs a `reqwest::RequestBuilder` for the `Target`, applying URL, method, query, headers, auth, body, timeout, and custom `request_fn`.
-    pub(crate) fn request_builder(&self, target: &T) -> Result<ProviderRequestBuilder, Error> {
-        let url = self.request_url(target);
-        let mut request_builder = self.client.request(target.method().into(), url.as_str());
+    /// Writes list and object query values with `style` for targets that don't set their own,
+    /// see `QueryParams::with_style`.
+    pub fn with_query_style(mut self, style: QueryStyle) -> Self {
+        self.query_style = style;
+        self
+    }
 
-        // apply query params
-        request_builder = request_builder.query(&target.query());
+    /// Fails requests fast while the circuit for their endpoint (or key) is open, see `CircuitBreaker`.
+    #[cfg(feature = "circuit")]
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker<T>) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
 
-        // apply headers
-        for (key, value) in target.headers() {
-            request_builder = request_builder.header(key, value);
-        }
+    /// Sends a second copy of slow idempotent requests, see `HedgePolicy`.
+    #[cfg(feature = "hedge")]
+    pub fn with_hedging(mut self, policy: HedgePolicy<T>) -> Self {
+        self.hedging = Some(policy);
+        self
+    }
+
+    /// Executes a built request for `target`, going through the circuit breaker and hedging policy if set.
+    #[cfg_attr(not(any(feature = "circuit", feature = "hedge")), allow(unused_variables))]
+    pub(crate) async fn send(&self, target: &T, request: reqwest::Request) -> Result<HTTPResponse, Error> {
+        #[cfg(feature = "circuit")]
+        let permit = match &self.circuit_breaker {
+            Some(breaker) => Some(breaker.acquire(breaker.key_for(target, request.url()))?),
+            None => None,
+        };
 
-        // apply authentication
-        if let Some(auth) = target.authentication() {
-            request_builder = match auth {
-                AuthMethod::Bearer(token) => request_builder.bearer_auth(token),
-                AuthMethod::Basic(username, password) => request_builder.basic_auth(username, password),
-                AuthMethod::Custom(auth_fn) => auth_fn(request_builder),
-            };
+        let execute = |request| execute_reqwest(&*self.transport, request);
+        #[cfg(feature = "hedge")]
+        let result = match &self.hedging {
+            Some(policy) if policy.applies_to(target) => policy.execute(request, execute).await,
+            _ => execute(request).await,
+        };
+        #[cfg(not(feature = "hedge"))]
+        let result = execute(request).await;
+
+        #[cfg(feature = "circuit")]
+        if let Some(permit) = permit {
+            permit.record(result.as_ref().is_ok_and(|response| !response.status().is_server_error()));
         }
+        result
+    }
 
-        // apply body
-        let body = target.body()?;
-        request_builder = request_builder.body(body.inner);
+    /// Builds the request this provider would send for `target`, without sending it.
+    /// Useful to inspect requests in tests; the timeout, if any, is kept in the extensions.
+    pub fn build_request(&self, target: &T) -> Result<http::Request<Bytes>, Error> {
+        from_reqwest_request(self.request_builder(target)?.build()?)
+    }
+
+    /// Constructs a `reqwest::RequestBuilder` for the `Target`, applying URL, method, query, headers, auth, body, timeout, and custom `request_fn`.
+    pub(crate) fn request_builder(&self, target: &T) -> Result<ProviderRequestBuilder, Error> {
+        let url = self.request_url(target)?;
+        let mut request_builder = target_request_builder(&self.client, url, target)?;
 
         // apply provider timeout
         if let Some(provider_timeout) = self.timeout {
@@ -310,22 +819,66 @@ where
     }
 }
 
+/// Applies a target's method, query, headers, authentication and body to a request for `url`.
+pub(crate) fn target_request_builder<T: Target + ?Sized>(
+    client: &reqwest::Client,
+    url: reqwest::Url,
+    target: &T,
+) -> Result<ProviderRequestBuilder, Error> {
+    // `url` already contains the query string
+    let mut request_builder = ProviderRequestBuilder::new(client.request(target.method().into(), url));
+
+    // apply headers
+    for (key, value) in target.headers() {
+        request_builder = request_builder.header(key, value);
+    }
+
+    // apply authentication
+    if let Some(auth) = target.authentication() {
+        request_builder = match auth {
+            AuthMethod::Bearer(token) => request_builder.bearer_auth(token),
+            AuthMethod::Basic(username, password) => request_builder.basic_auth(username, password),
+            AuthMethod::Custom(auth_fn) => auth_fn(request_builder),
+        };
+    }
+
+    // apply body
+    let body = target.body()?;
+    Ok(request_builder.body(body.inner))
+}
+
+/// Client used to build requests outside of a `Provider`, e.g. by `Target::to_http_request`.
+pub(crate) fn shared_builder_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(reqwest::Client::new)
+}
+
 impl<T> Default for Provider<T>
 where
     T: Target,
 {
     fn default() -> Self {
-        #[cfg(not(feature = "middleware"))]
         let client = reqwest::Client::new();
-        #[cfg(feature = "middleware")]
-        let client = {
-            MiddlewareClientBuilder::new(reqwest::Client::new()).build()
-        };
         Self {
+            transport: Arc::new(DefaultTransport::new(client.clone())),
             client,
             endpoint_fn: None,
             request_fn: None,
             timeout: None,
+            base_url: None,
+            query_style: QueryStyle::default(),
+            #[cfg(feature = "circuit")]
+            circuit_breaker: None,
+            #[cfg(feature = "hedge")]
+            hedging: None,
+            #[cfg(feature = "jsonrpc")]
+            id_generator: Arc::new(SequentialIdGenerator::default()),
+            #[cfg(feature = "jsonrpc")]
+            batch_concurrency: None,
+            #[cfg(feature = "jsonrpc")]
+            batch_limits: BatchLimits::default(),
+            #[cfg(feature = "jsonrpc")]
+            learned_batch_items: AtomicUsize::new(usize::MAX),
         }
     }
 }
@@ -338,6 +891,8 @@ mod tests {
         provider::{JsonProviderType, Provider},
         target::Target,
     };
+    #[cfg(feature = "jsonrpc")]
+    use crate::test_util::{self, JsonBody, TestTarget};
     use serde::{Deserialize, Serialize};
     use std::{borrow::Cow, collections::hash_map::DefaultHasher, collections::HashMap, hash::{Hash, Hasher}, time::{Duration, UNIX_EPOCH}};
 
@@ -419,14 +974,22 @@ mod tests {
     #[test]
     fn test_test_endpoint_closure() {
         let provider = Provider::<HttpBin>::default();
-        assert_eq!(
-            provider.request_url(&HttpBin::Get),
-            "https://httpbin.org/get?ts=1728044812"
-        );
+        let url = provider.request_url(&HttpBin::Get).unwrap();
+        assert_eq!(url.as_str(), "https://httpbin.org/get?ts=1728044812&foo=bar");
+        assert_eq!(url, HttpBin::Get.absolute_url().unwrap());
+        let request = provider.build_request(&HttpBin::Get).unwrap();
+        assert_eq!(request.uri(), "https://httpbin.org/get?ts=1728044812&foo=bar");
 
         let provider =
             Provider::<HttpBin>::new(Some(|_: &HttpBin| "http://httpbin.org".to_string()), None, None);
-        assert_eq!(provider.request_url(&HttpBin::Post), "http://httpbin.org");
+        assert_eq!(provider.request_url(&HttpBin::Post).unwrap().as_str(), "http://httpbin.org/?foo=bar");
+
+        let provider = Provider::<HttpBin>::default().with_base_url("http://localhost:8080/");
+        assert_eq!(provider.request_url(&HttpBin::Post).unwrap().as_str(), "http://localhost:8080/post?foo=bar");
+
+        let provider = Provider::<HttpBin>::default().with_base_url("localhost:8080");
+        let err = provider.build_request(&HttpBin::Post).unwrap_err();
+        assert!(matches!(err, Error::InvalidUrl { .. }), "{}", err);
     }
 
     #[test]
@@ -454,30 +1017,363 @@ mod tests {
         assert_eq!(headers.get("X-hash").unwrap(), "3270317559611782182");
     }
 
+    #[test]
+    fn test_to_http_request() {
+        let request = HttpBin::Post.to_http_request().unwrap();
+        assert_eq!(request.method(), http::Method::POST);
+        assert_eq!(request.uri(), "https://httpbin.org/post?foo=bar");
+        let person: Person = serde_json::from_slice(request.body()).unwrap();
+        assert_eq!(person.name, "test");
+
+        let request = HttpBin::HeaderAuth.to_http_request().unwrap();
+        assert_eq!(request.headers()["X-Test-Api-Key"], "my-secret-key");
+
+        let provider = Provider::<HttpBin>::new(None, None, Some(Duration::from_secs(5)));
+        let built = provider.build_request(&HttpBin::Bearer).unwrap();
+        let converted = HttpBin::Bearer.to_http_request().unwrap();
+        assert_eq!(built.uri(), converted.uri());
+        assert_eq!(built.headers(), converted.headers());
+        assert_eq!(built.headers()["authorization"], "Bearer token");
+    }
+
     #[tokio::test]
     async fn test_authentication() {
-        let provider = Provider::<HttpBin>::default();
+        let server = crate::testing::MockServer::start().await;
+        let mock = server
+            .expect(HttpBin::Bearer)
+            .respond_json(serde_json::json!({ "authenticated": true }))
+            .times(1);
+        let provider: Provider<HttpBin> = server.provider();
         let response: serde_json::Value = provider
             .request_json(HttpBin::Bearer)
             .await
             .expect("request error");
 
         assert!(response["authenticated"].as_bool().unwrap());
+        assert_eq!(mock.requests()[0].headers()["authorization"], "Bearer token");
     }
 
     #[tokio::test]
     async fn test_header_api_key_auth() {
-        let provider = Provider::<HttpBin>::default();
-        let response: serde_json::Value = provider
-            .request_json(HttpBin::HeaderAuth)
+        let server = crate::testing::MockServer::start().await;
+        let mock = server.expect(HttpBin::HeaderAuth).respond_status(200).times(1);
+        let provider: Provider<HttpBin> = server.provider();
+        provider
+            .request(HttpBin::HeaderAuth)
             .await
-            .expect("request error");
+            .expect("request error")
+            .error_for_status()
+            .expect("unexpected status");
+
+        let headers = mock.requests()[0].headers().clone();
+        assert_eq!(headers["x-test-api-key"], "my-secret-key");
+    }
 
-        // httpbin /headers returns a JSON object like: {"headers": {"Header-Name": "Header-Value", ...}}
-        let headers_map = response.get("headers").unwrap().as_object().unwrap();
-        assert_eq!(
-            headers_map.get("X-Test-Api-Key").unwrap().as_str().unwrap(),
-            "my-secret-key"
+    #[tokio::test]
+    async fn test_streaming_request_body() {
+        use crate::test_util::{self, TestTarget};
+
+        let server = test_util::serve(|request| (200, String::from_utf8(request.body().to_vec()).unwrap())).await;
+        let provider = Provider::<TestTarget>::new(
+            None,
+            Some(Box::new(|builder: &ProviderRequestBuilder, _: &TestTarget| {
+                builder.try_clone().unwrap().body(reqwest::Body::wrap(String::from("\"streamed\"")))
+            })),
+            None,
         );
+
+        let body: String = provider.request_json(TestTarget::post(server.url(), "/upload")).await.unwrap();
+        assert_eq!(body, "streamed");
+    }
+
+    #[tokio::test]
+    async fn test_in_memory_transport() {
+        let transport = crate::transport::InMemoryTransport::new(|request| {
+            let authorization = request.headers().get("authorization").map_or("", |value| value.to_str().unwrap());
+            http::Response::new(format!("{{\"authorization\":\"{}\"}}", authorization).into())
+        });
+        let provider = Provider::<HttpBin>::new(None, None, Some(Duration::from_secs(5)))
+            .with_transport(transport.clone());
+
+        let response: serde_json::Value = provider.request_json(HttpBin::Bearer).await.unwrap();
+        assert_eq!(response["authorization"], "Bearer token");
+
+        provider.request(HttpBin::Post).await.unwrap();
+
+        let requests = transport.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].uri(), "https://httpbin.org/bearer?foo=bar");
+        assert_eq!(requests[1].method(), http::Method::POST);
+        let sent: Person = serde_json::from_slice(requests[1].body()).unwrap();
+        assert_eq!(sent.name, "test");
+    }
+
+    #[tokio::test]
+    async fn test_request_builder_extensions() {
+        #[derive(Clone)]
+        struct Tenant(&'static str);
+
+        let transport = crate::transport::InMemoryTransport::new(|request| {
+            let tenant = request.extensions().get::<Tenant>().map_or("none", |tenant| tenant.0);
+            http::Response::new(format!("\"{}\"", tenant).into())
+        });
+        let provider = Provider::<HttpBin>::new(
+            None,
+            Some(Box::new(|builder: &ProviderRequestBuilder, _: &HttpBin| {
+                builder.try_clone().unwrap().with_extension(Tenant("acme"))
+            })),
+            None,
+        )
+        .with_transport(transport);
+
+        let tenant: String = provider.request_json(HttpBin::Get).await.unwrap();
+        assert_eq!(tenant, "acme");
+    }
+
+    /// Answers every JSON-RPC request with its own method name as the result, skipping notifications.
+    #[cfg(feature = "jsonrpc")]
+    fn echo_method(request: &http::Request<bytes::Bytes>) -> (u16, String) {
+        let answer = |req: &serde_json::Value| {
+            serde_json::json!({"jsonrpc": "2.0", "id": req["id"], "result": req["method"]})
+        };
+        let body = match request.json() {
+            serde_json::Value::Array(requests) => {
+                let answers = requests
+                    .iter()
+                    .filter(|req| req.get("id").is_some())
+                    .map(answer)
+                    .collect::<Vec<_>>();
+                if answers.is_empty() {
+                    return (200, String::new());
+                }
+                serde_json::Value::Array(answers)
+            }
+            single if single.get("id").is_none() => return (200, String::new()),
+            single => answer(&single),
+        };
+        (200, body.to_string())
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_ids_are_unique_across_batches() {
+        use crate::jsonrpc::{JsonRpcId, SequentialIdGenerator};
+        use crate::provider::JsonRpcProviderType;
+
+        let server = test_util::serve(echo_method).await;
+        let provider = Provider::<TestTarget>::default().with_id_generator(SequentialIdGenerator::starting_at(10));
+        let targets = || {
+            vec![
+                TestTarget::rpc(server.url(), "a", vec![]),
+                TestTarget::rpc(server.url(), "b", vec![]),
+            ]
+        };
+
+        let first: Vec<JsonRpcResult<String>> = provider.batch(targets()).await.unwrap();
+        let second: Vec<JsonRpcResult<String>> = provider.batch_chunk_by(targets(), 1).await.unwrap();
+        let single: JsonRpcResult<String> = provider.call(TestTarget::rpc(server.url(), "c", vec![])).await.unwrap();
+
+        let ids = first
+            .iter()
+            .chain(second.iter())
+            .chain(std::iter::once(&single))
+            .map(|result| result.id().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, (10..15).map(JsonRpcId::Integer).collect::<Vec<_>>());
+        assert!(matches!(single, JsonRpcResult::Value(ref response) if response.result == "c"));
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_custom_string_ids() {
+        use crate::jsonrpc::JsonRpcId;
+        use crate::provider::JsonRpcProviderType;
+
+        let server = test_util::serve(echo_method).await;
+        let provider = Provider::<TestTarget>::default().with_id_generator(|| JsonRpcId::from("fixed"));
+        let result: JsonRpcResult<String> = provider.call(TestTarget::rpc(server.url(), "a", vec![])).await.unwrap();
+
+        assert_eq!(result.id(), &JsonRpcId::String("fixed".into()));
+        assert_eq!(server.received_requests()[0].json()["id"], "fixed");
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_batch_auto_with_repeating_ids() {
+        use crate::jsonrpc::{BatchLimits, JsonRpcId};
+        use crate::provider::JsonRpcProviderType;
+
+        let server = test_util::serve(echo_method).await;
+        let provider = Provider::<TestTarget>::default()
+            .with_id_generator(|| JsonRpcId::from("fixed"))
+            .with_batch_limits(BatchLimits { max_items: 2, max_bytes: 1024 * 1024 });
+        let methods = ["a", "b", "c"];
+
+        let results: Vec<JsonRpcResult<String>> = provider
+            .batch_auto(methods.iter().map(|m| TestTarget::rpc(server.url(), m, vec![])).collect())
+            .await
+            .unwrap();
+        let answers = results
+            .iter()
+            .map(|result| match result {
+                JsonRpcResult::Value(response) => response.result.clone(),
+                JsonRpcResult::Error(error) => panic!("unexpected error: {}", error.error),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(answers, methods);
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_notifications() {
+        use crate::provider::JsonRpcProviderType;
+
+        let server = test_util::serve(echo_method).await;
+        let provider = Provider::<TestTarget>::default();
+
+        provider.notify(TestTarget::rpc(server.url(), "log", vec![])).await.unwrap();
+        assert!(server.received_requests()[0].json().get("id").is_none());
+
+        let results: Vec<JsonRpcResult<String>> = provider
+            .batch(vec![
+                TestTarget::rpc(server.url(), "a", vec![]),
+                TestTarget::rpc(server.url(), "log", vec![]).notification(),
+                TestTarget::rpc(server.url(), "b", vec![]),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(server.received_requests()[1].json().as_array().unwrap().len(), 3);
+
+        let results: Vec<JsonRpcResult<String>> = provider
+            .batch(vec![TestTarget::rpc(server.url(), "log", vec![]).notification()])
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_batch_concurrency_limit() {
+        use crate::provider::JsonRpcProviderType;
+        use std::sync::{atomic::{AtomicUsize, Ordering}, Arc};
+
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let (current, max) = (in_flight.clone(), max_in_flight.clone());
+        let server = test_util::serve(move |request| {
+            let now = current.fetch_add(1, Ordering::SeqCst) + 1;
+            max.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(50));
+            current.fetch_sub(1, Ordering::SeqCst);
+            echo_method(request)
+        })
+        .await;
+
+        let provider = Provider::<TestTarget>::default().with_batch_concurrency(2);
+        let targets = (0..6).map(|_| TestTarget::rpc(server.url(), "a", vec![])).collect::<Vec<_>>();
+        let results: Vec<JsonRpcResult<String>> = provider.batch_chunk_by(targets, 1).await.unwrap();
+
+        assert_eq!(results.len(), 6);
+        assert!(max_in_flight.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_batch_chunk_outcomes() {
+        use crate::provider::JsonRpcProviderType;
+
+        // chunks containing "bad" get a non JSON-RPC response
+        let server = test_util::serve(|request| {
+            if request.json().to_string().contains("bad") {
+                (500, "oops".into())
+            } else {
+                echo_method(request)
+            }
+        })
+        .await;
+        let provider = Provider::<TestTarget>::default().with_batch_concurrency(1);
+        let targets = || {
+            vec![
+                TestTarget::rpc(server.url(), "a", vec![]),
+                TestTarget::rpc(server.url(), "bad", vec![]),
+                TestTarget::rpc(server.url(), "b", vec![]),
+            ]
+        };
+
+        let outcomes = provider.batch_chunk_outcomes::<String>(targets(), 1, false).await.unwrap();
+        assert_eq!(outcomes.iter().map(|o| o.is_ok()).collect::<Vec<_>>(), vec![true, false, true]);
+        assert_eq!(outcomes[1].offset, 1);
+        assert_eq!(outcomes[1].targets[0].method_name(), "bad");
+        assert!(provider.batch_chunk_by::<String>(targets(), 1).await.is_err());
+
+        let requests_before = server.received_requests().len();
+        let mut reordered = targets();
+        reordered.swap(0, 1);
+        let outcomes = provider.batch_chunk_outcomes::<String>(reordered, 1, true).await.unwrap();
+        assert!(outcomes.iter().all(|o| !o.is_ok()));
+        assert_eq!(server.received_requests().len(), requests_before + 1);
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_batch_auto_halves_and_keeps_order() {
+        use crate::jsonrpc::BatchLimits;
+        use crate::provider::JsonRpcProviderType;
+
+        // rejects batches of more than 2 calls, and answers in reverse order
+        let server = test_util::serve(|request| {
+            let batch = request.json();
+            if batch.as_array().unwrap().len() > 2 {
+                return (413, String::new());
+            }
+            let (status, body) = echo_method(request);
+            let mut answers: Vec<serde_json::Value> = serde_json::from_str(&body).unwrap();
+            answers.reverse();
+            (status, serde_json::Value::Array(answers).to_string())
+        })
+        .await;
+        let provider = Provider::<TestTarget>::default().with_batch_limits(BatchLimits { max_items: 8, max_bytes: 1024 * 1024 });
+        let methods = ["a", "b", "c", "d", "e"];
+
+        let results: Vec<JsonRpcResult<String>> = provider
+            .batch_auto(methods.iter().map(|m| TestTarget::rpc(server.url(), m, vec![])).collect())
+            .await
+            .unwrap();
+        let answers = results
+            .iter()
+            .map(|result| match result {
+                JsonRpcResult::Value(response) => response.result.clone(),
+                JsonRpcResult::Error(error) => panic!("unexpected error: {}", error.error),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(answers, methods);
+
+        // the learned limit is reused, no more rejected batches
+        let requests_before = server.received_requests().len();
+        let _: Vec<JsonRpcResult<String>> = provider
+            .batch_auto(methods.iter().map(|m| TestTarget::rpc(server.url(), m, vec![])).collect())
+            .await
+            .unwrap();
+        assert_eq!(server.received_requests().len() - requests_before, 3);
+        assert_eq!(provider.learned_batch_items.load(Ordering::Relaxed), 2);
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_batch_auto_learns_only_accepted_sizes() {
+        use crate::provider::JsonRpcProviderType;
+
+        // rejects the full batch as too large, then fails the halves for another reason
+        let server = test_util::serve(|request| match request.json().as_array().unwrap().len() {
+            4 => (413, String::new()),
+            _ => (500, String::new()),
+        })
+        .await;
+        let provider = Provider::<TestTarget>::default();
+
+        let targets = ["a", "b", "c", "d"].iter().map(|m| TestTarget::rpc(server.url(), m, vec![])).collect();
+        assert!(provider.batch_auto::<String>(targets).await.is_err());
+        assert_eq!(provider.learned_batch_items.load(Ordering::Relaxed), usize::MAX);
     }
 }
@@ -0,0 +1,321 @@
+//! Ordered query parameters and percent-encoded query strings, including repeated and structured
+//! values.
+use crate::error::Error;
+use std::collections::HashMap;
+
+/// A query parameter value: a single value, a list (`?id=1&id=2`) or an object (`?filter[status]=open`).
+/// How lists and objects are written is chosen by `QueryStyle`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QueryValue {
+    Single(String),
+    List(Vec<String>),
+    /// Ordered entries of an object.
+    Map(Vec<(String, String)>),
+}
+
+impl QueryValue {
+    pub fn list<V: Into<String>>(values: impl IntoIterator<Item = V>) -> Self {
+        QueryValue::List(values.into_iter().map(Into::into).collect())
+    }
+
+    pub fn map<K: Into<String>, V: Into<String>>(entries: impl IntoIterator<Item = (K, V)>) -> Self {
+        QueryValue::Map(entries.into_iter().map(|(key, value)| (key.into(), value.into())).collect())
+    }
+}
+
+impl From<&str> for QueryValue {
+    fn from(value: &str) -> Self {
+        QueryValue::Single(value.into())
+    }
+}
+
+impl From<String> for QueryValue {
+    fn from(value: String) -> Self {
+        QueryValue::Single(value)
+    }
+}
+
+impl From<&String> for QueryValue {
+    fn from(value: &String) -> Self {
+        QueryValue::Single(value.clone())
+    }
+}
+
+/// How list values are written, e.g. for `ids = [1, 2]`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ArrayStyle {
+    /// `ids=1&ids=2`
+    #[default]
+    Repeat,
+    /// `ids[]=1&ids[]=2` (brackets are percent-encoded)
+    Brackets,
+    /// `ids=1,2`
+    Comma,
+}
+
+/// How object values are written, e.g. for `filter = { status: open, owner: me }`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ObjectStyle {
+    /// `filter[status]=open&filter[owner]=me`, OpenAPI `deepObject` (brackets are percent-encoded).
+    #[default]
+    DeepObject,
+    /// `status=open&owner=me`, OpenAPI `form` with `explode`.
+    Form,
+    /// `filter=status,open,owner,me`, OpenAPI `form` without `explode`.
+    Comma,
+}
+
+/// Serialization styles for list and object query values, set per target with
+/// `QueryParams::with_style` or per provider with `Provider::with_query_style`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryStyle {
+    pub array: ArrayStyle,
+    pub object: ObjectStyle,
+}
+
+/// Query parameters in the order they are sent, see `Target::query_params`.
+///
+/// Build them from pairs (`Vec<(K, V)>`, arrays, an ordered map such as `IndexMap` via
+/// `collect`), or from any flat `Serialize` struct with `from_serialize`. Parameters from a
+/// `HashMap` are sorted by key so the query string is deterministic. Keys may repeat, and
+/// values may be lists or objects (see `QueryValue`).
+///
+/// ```rust,ignore
+/// fn query_params(&self) -> QueryParams {
+///     QueryParams::from([("q", "rust & http"), ("page", "2")])
+///         .with("id", QueryValue::list(["1", "2"]))
+///         .with("filter", QueryValue::map([("status", "open")]))
+///         .with_style(QueryStyle { array: ArrayStyle::Brackets, ..Default::default() })
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryParams {
+    params: Vec<(String, QueryValue)>,
+    style: Option<QueryStyle>,
+}
+
+impl QueryParams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serializes a flat struct (or map, or sequence of pairs) with `serde_urlencoded`, keeping
+    /// field order. Use `QueryValue::list` and `QueryValue::map` for structured values.
+    pub fn from_serialize<S: serde::Serialize>(value: &S) -> Result<Self, Error> {
+        let encoded = serde_urlencoded::to_string(value)?;
+        let pairs = serde_urlencoded::from_str::<Vec<(String, String)>>(&encoded)
+            .expect("serde_urlencoded output is a valid query string");
+        Ok(pairs.into_iter().collect())
+    }
+
+    /// Appends a parameter after the existing ones; a key may be appended several times.
+    pub fn append(&mut self, key: impl Into<String>, value: impl Into<QueryValue>) {
+        self.params.push((key.into(), value.into()));
+    }
+
+    /// Builder-style `append`.
+    pub fn with(mut self, key: impl Into<String>, value: impl Into<QueryValue>) -> Self {
+        self.append(key, value);
+        self
+    }
+
+    /// Writes these parameters with `style`, whatever the provider's style.
+    pub fn with_style(mut self, style: QueryStyle) -> Self {
+        self.style = Some(style);
+        self
+    }
+
+    pub fn style(&self) -> Option<QueryStyle> {
+        self.style
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.params.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.params.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &QueryValue)> {
+        self.params.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    /// The `application/x-www-form-urlencoded` query string, without the leading `?`, written
+    /// with this query's style or the default one.
+    pub fn to_query_string(&self) -> String {
+        self.encode(QueryStyle::default())
+    }
+
+    /// Like `to_query_string`, with `default_style` unless this query sets its own style.
+    pub fn encode(&self, default_style: QueryStyle) -> String {
+        let style = self.style.unwrap_or(default_style);
+        let pair = |key: &str, value: &str| format!("{}={}", encode(key), encode(value));
+        let mut parts = Vec::new();
+
+        for (key, value) in &self.params {
+            match value {
+                QueryValue::Single(value) => parts.push(pair(key, value)),
+                QueryValue::List(values) => match style.array {
+                    ArrayStyle::Repeat => parts.extend(values.iter().map(|value| pair(key, value))),
+                    ArrayStyle::Brackets => parts.extend(values.iter().map(|value| pair(&format!("{}[]", key), value))),
+                    ArrayStyle::Comma => parts.push(format!("{}={}", encode(key), join_encoded(values.iter()))),
+                },
+                QueryValue::Map(entries) => match style.object {
+                    ObjectStyle::DeepObject => {
+                        parts.extend(entries.iter().map(|(name, value)| pair(&format!("{}[{}]", key, name), value)))
+                    }
+                    ObjectStyle::Form => parts.extend(entries.iter().map(|(name, value)| pair(name, value))),
+                    ObjectStyle::Comma => {
+                        let flattened = entries.iter().flat_map(|(name, value)| [name, value]);
+                        parts.push(format!("{}={}", encode(key), join_encoded(flattened)));
+                    }
+                },
+            }
+        }
+        parts.join("&")
+    }
+}
+
+fn encode(component: &str) -> String {
+    form_urlencoded::byte_serialize(component.as_bytes()).collect()
+}
+
+/// Encodes each value and joins them with literal commas.
+fn join_encoded<'a>(values: impl Iterator<Item = &'a String>) -> String {
+    values.map(|value| encode(value)).collect::<Vec<_>>().join(",")
+}
+
+impl std::fmt::Display for QueryParams {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.to_query_string())
+    }
+}
+
+impl<K: Into<String>, V: Into<QueryValue>> FromIterator<(K, V)> for QueryParams {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self {
+            params: iter.into_iter().map(|(key, value)| (key.into(), value.into())).collect(),
+            style: None,
+        }
+    }
+}
+
+impl<K: Into<String>, V: Into<QueryValue>> From<Vec<(K, V)>> for QueryParams {
+    fn from(pairs: Vec<(K, V)>) -> Self {
+        pairs.into_iter().collect()
+    }
+}
+
+impl<K: Into<String>, V: Into<QueryValue>, const N: usize> From<[(K, V); N]> for QueryParams {
+    fn from(pairs: [(K, V); N]) -> Self {
+        pairs.into_iter().collect()
+    }
+}
+
+impl From<HashMap<String, String>> for QueryParams {
+    fn from(map: HashMap<String, String>) -> Self {
+        let mut pairs = map.into_iter().collect::<Vec<_>>();
+        pairs.sort();
+        pairs.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize)]
+    struct Search {
+        q: &'static str,
+        page: u32,
+        lang: Option<&'static str>,
+    }
+
+    #[test]
+    fn test_encoding_and_order() {
+        let query = QueryParams::from([("q", "rust & http"), ("emoji", "🦀"), ("a", "1")]);
+        assert_eq!(query.to_query_string(), "q=rust+%26+http&emoji=%F0%9F%A6%80&a=1");
+
+        let map = HashMap::from([("b".to_string(), "2".to_string()), ("a".to_string(), "1".to_string())]);
+        assert_eq!(QueryParams::from(map).to_string(), "a=1&b=2");
+
+        let search = Search { q: "a=b", page: 2, lang: None };
+        assert_eq!(QueryParams::from_serialize(&search).unwrap().to_string(), "q=a%3Db&page=2");
+    }
+
+    #[test]
+    fn test_structured_styles() {
+        let query = QueryParams::new()
+            .with("id", "1")
+            .with("id", "2")
+            .with("tag", QueryValue::list(["a b", "c,d"]))
+            .with("filter", QueryValue::map([("status", "open"), ("owner", "me")]));
+        let encode = |array, object| query.encode(QueryStyle { array, object });
+
+        assert_eq!(
+            encode(ArrayStyle::Repeat, ObjectStyle::DeepObject),
+            "id=1&id=2&tag=a+b&tag=c%2Cd&filter%5Bstatus%5D=open&filter%5Bowner%5D=me"
+        );
+        assert_eq!(
+            encode(ArrayStyle::Brackets, ObjectStyle::Form),
+            "id=1&id=2&tag%5B%5D=a+b&tag%5B%5D=c%2Cd&status=open&owner=me"
+        );
+        assert_eq!(
+            encode(ArrayStyle::Comma, ObjectStyle::Comma),
+            "id=1&id=2&tag=a+b,c%2Cd&filter=status,open,owner,me"
+        );
+
+        let own_style = query.clone().with_style(QueryStyle { array: ArrayStyle::Comma, ..Default::default() });
+        assert!(own_style.encode(QueryStyle::default()).contains("tag=a+b,c%2Cd"));
+    }
+
+    struct Issues;
+
+    impl crate::target::Target for Issues {
+        fn base_url(&self) -> std::borrow::Cow<'_, str> {
+            "https://tracker.example".into()
+        }
+
+        fn method(&self) -> crate::http::HTTPMethod {
+            crate::http::HTTPMethod::GET
+        }
+
+        fn path(&self) -> String {
+            "/issues".into()
+        }
+
+        fn query(&self) -> HashMap<String, String> {
+            HashMap::default()
+        }
+
+        fn query_params(&self) -> QueryParams {
+            QueryParams::new().with("label", QueryValue::list(["bug", "ui"]))
+        }
+
+        fn headers(&self) -> HashMap<String, String> {
+            HashMap::default()
+        }
+
+        fn authentication(&self) -> Option<crate::http::AuthMethod> {
+            None
+        }
+
+        fn body(&self) -> Result<crate::http::HTTPBody, Error> {
+            Ok(crate::http::HTTPBody::default())
+        }
+    }
+
+    #[test]
+    fn test_provider_query_style() {
+        use crate::{provider::Provider, target::Target};
+
+        let provider = Provider::<Issues>::default();
+        let url = provider.request_url(&Issues).unwrap();
+        assert_eq!(url.as_str(), "https://tracker.example/issues?label=bug&label=ui");
+        assert_eq!(url, Issues.absolute_url().unwrap());
+
+        let provider = provider.with_query_style(QueryStyle { array: ArrayStyle::Comma, ..Default::default() });
+        assert_eq!(provider.build_request(&Issues).unwrap().uri(), "https://tracker.example/issues?label=bug,ui");
+    }
+}
@@ -0,0 +1,389 @@
+//! Quorum reads: fan a JSON-RPC call out to several independent providers and only
+//! accept a result enough of them agree on.
+use crate::{
+    jsonrpc::{JsonRpcError, JsonRpcResult},
+    provider::{Provider, match_batch_results},
+    target::JsonRpcTarget,
+    transport::{DefaultTransport, Transport},
+};
+use futures::future::join_all;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use thiserror::Error;
+
+/// How many providers must return an equal result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quorum {
+    /// More than half of the providers.
+    Majority,
+    /// Every provider.
+    All,
+    AtLeast(usize),
+}
+
+impl Quorum {
+    fn required(&self, members: usize) -> usize {
+        match self {
+            Quorum::Majority => members / 2 + 1,
+            Quorum::All => members,
+            Quorum::AtLeast(n) => *n,
+        }
+    }
+}
+
+/// What one endpoint answered: its result, or the JSON-RPC / transport error it returned. A result
+/// that does not deserialize into the requested type is reported as an internal error.
+#[derive(Debug, Clone)]
+pub struct EndpointAnswer {
+    pub endpoint: String,
+    pub answer: Result<Value, JsonRpcError>,
+}
+
+#[derive(Debug, Error)]
+pub enum QuorumError {
+    #[error("Invalid Request")]
+    InvalidRequest,
+
+    /// Fewer than `required` endpoints agreed; `index` is the position within a batch.
+    #[error("No quorum ({required} required) for call {index:?}: {}", format_answers(.answers))]
+    NoAgreement {
+        required: usize,
+        index: Option<usize>,
+        answers: Vec<EndpointAnswer>,
+    },
+
+}
+
+fn format_answers(answers: &[EndpointAnswer]) -> String {
+    answers
+        .iter()
+        .map(|answer| match &answer.answer {
+            Ok(value) => format!("{} => {}", answer.endpoint, value),
+            Err(error) => format!("{} => error {}", answer.endpoint, error),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Sends each call to every member provider and returns the result a `Quorum` of them agree on.
+/// Results are deserialized first and compared with `PartialEq`, or with the equality passed to
+/// `call_by` / `batch_by` (e.g. to ignore hex case or fields that legitimately differ between nodes).
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let provider = QuorumProvider::from_urls(
+///     ["https://node-a.example", "https://node-b.example", "https://node-c.example"],
+///     Quorum::Majority,
+/// );
+/// let balance: String = provider
+///     .call_by(EthereumRPC::GetBalance(address), |a: &String, b: &String| a.eq_ignore_ascii_case(b))
+///     .await?;
+/// ```
+pub struct QuorumProvider<T: JsonRpcTarget, X: Transport = DefaultTransport> {
+    members: Vec<(String, Provider<T, X>)>,
+    quorum: Quorum,
+}
+
+impl<T: JsonRpcTarget, X: Transport> std::fmt::Debug for QuorumProvider<T, X> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("QuorumProvider")
+            .field("members", &self.members.iter().map(|(name, _)| name).collect::<Vec<_>>())
+            .field("quorum", &self.quorum)
+            .finish()
+    }
+}
+
+impl<T: JsonRpcTarget> QuorumProvider<T> {
+    /// One default provider per base URL.
+    pub fn from_urls<I, S>(urls: I, quorum: Quorum) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        urls.into_iter().fold(Self::new(quorum), |quorum, url| {
+            let url = url.into();
+            quorum.with_member(url.clone(), Provider::default().with_base_url(url))
+        })
+    }
+}
+
+impl<T: JsonRpcTarget, X: Transport> QuorumProvider<T, X> {
+    pub fn new(quorum: Quorum) -> Self {
+        Self {
+            members: Vec::new(),
+            quorum,
+        }
+    }
+
+    /// Adds a provider, identified by `name` in disagreement errors.
+    pub fn with_member(mut self, name: impl Into<String>, provider: Provider<T, X>) -> Self {
+        self.members.push((name.into(), provider));
+        self
+    }
+
+    /// Sends `target` to every member and returns the agreed result.
+    pub async fn call<U: DeserializeOwned + PartialEq>(&self, target: T) -> Result<U, QuorumError> {
+        self.call_by(target, U::eq).await
+    }
+
+    /// Like `call`, comparing results with `eq`; the first result of the agreeing group is returned.
+    pub async fn call_by<U, F>(&self, target: T, eq: F) -> Result<U, QuorumError>
+    where
+        U: DeserializeOwned,
+        F: Fn(&U, &U) -> bool,
+    {
+        let answers = join_all(self.members.iter().map(|(name, provider)| {
+            let target = &target;
+            async move {
+                let answer = match provider.call_ref::<Value>(target).await {
+                    Ok(JsonRpcResult::Value(response)) => Ok(response.result),
+                    Ok(JsonRpcResult::Error(response)) => Err(response.error),
+                    Err(error) => Err(error),
+                };
+                EndpointAnswer {
+                    endpoint: name.clone(),
+                    answer,
+                }
+            }
+        }))
+        .await;
+
+        self.agree(answers, None, &eq)
+    }
+
+    /// Sends the batch to every member and returns the agreed result of each call, in input order.
+    /// Fails if any single call lacks a quorum.
+    pub async fn batch<U: DeserializeOwned + PartialEq>(&self, targets: Vec<T>) -> Result<Vec<U>, QuorumError> {
+        self.batch_by(targets, U::eq).await
+    }
+
+    /// Like `batch`, comparing results with `eq`.
+    pub async fn batch_by<U, F>(&self, targets: Vec<T>, eq: F) -> Result<Vec<U>, QuorumError>
+    where
+        U: DeserializeOwned,
+        F: Fn(&U, &U) -> bool,
+    {
+        if targets.is_empty() {
+            return Err(QuorumError::InvalidRequest);
+        }
+
+        let per_member = join_all(self.members.iter().map(|(_, provider)| {
+            let targets = &targets;
+            async move {
+                let requests = provider.rpc_requests(targets);
+                let results = provider.send_rpc_batch::<Value>(&targets[0], &requests).await;
+                let results = match results {
+                    Ok(results) => results,
+                    Err(error) => {
+                        return requests
+                            .iter()
+                            .filter(|request| !request.is_notification())
+                            .map(|_| Err(error.clone()))
+                            .collect::<Vec<_>>();
+                    }
+                };
+                // order answers like the requests, skipping notifications
+                match_batch_results(&requests, results)
+                    .into_iter()
+                    .map(|result| match result {
+                        JsonRpcResult::Value(response) => Ok(response.result),
+                        JsonRpcResult::Error(response) => Err(response.error),
+                    })
+                    .collect::<Vec<_>>()
+            }
+        }))
+        .await;
+
+        let calls = per_member.first().map_or(0, Vec::len);
+        let mut per_member = per_member.into_iter().map(Vec::into_iter).collect::<Vec<_>>();
+        (0..calls)
+            .map(|index| {
+                let answers = self
+                    .members
+                    .iter()
+                    .zip(per_member.iter_mut())
+                    .map(|((name, _), answers)| EndpointAnswer {
+                        endpoint: name.clone(),
+                        answer: answers.next().expect("every member answers every call"),
+                    })
+                    .collect();
+                self.agree(answers, Some(index), &eq)
+            })
+            .collect()
+    }
+
+    fn agree<U, F>(&self, mut answers: Vec<EndpointAnswer>, index: Option<usize>, eq: &F) -> Result<U, QuorumError>
+    where
+        U: DeserializeOwned,
+        F: Fn(&U, &U) -> bool,
+    {
+        let required = self.quorum.required(self.members.len()).max(1);
+
+        // deserialize every successful answer, turning the ones that don't fit `U` into errors
+        let mut typed = Vec::with_capacity(answers.len());
+        for endpoint in answers.iter_mut() {
+            let Ok(value) = &endpoint.answer else { continue };
+            match serde_json::from_value::<U>(value.clone()) {
+                Ok(result) => typed.push(result),
+                Err(error) => {
+                    let message = format!("Failed to deserialize result {}: {}", value, error);
+                    endpoint.answer = Err(JsonRpcError::internal_error(message));
+                }
+            }
+        }
+
+        // group equal results, remembering the first of each group
+        let mut groups: Vec<(usize, usize)> = Vec::new();
+        for (position, result) in typed.iter().enumerate() {
+            match groups.iter_mut().find(|(first, _)| eq(&typed[*first], result)) {
+                Some((_, count)) => *count += 1,
+                None => groups.push((position, 1)),
+            }
+        }
+
+        match groups.into_iter().find(|(_, count)| *count >= required) {
+            Some((first, _)) => Ok(typed.swap_remove(first)),
+            None => Err(QuorumError::NoAgreement {
+                required,
+                index,
+                answers,
+            }),
+        }
+    }
+}
+
+impl From<QuorumError> for JsonRpcError {
+    fn from(err: QuorumError) -> Self {
+        JsonRpcError::internal_error(err.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        test_util::{self, JsonBody, TestTarget},
+        testing::MockServer,
+    };
+
+    fn block_number() -> TestTarget {
+        TestTarget::rpc("http://unused.invalid", "eth_blockNumber", vec![])
+    }
+
+    /// Answers every call (single or batched) with `result`.
+    async fn node(result: &'static str) -> MockServer {
+        test_util::serve(move |request| {
+            let answer = |call: &Value| serde_json::json!({ "jsonrpc": "2.0", "id": call["id"], "result": result });
+            let body = match request.json() {
+                Value::Array(calls) => Value::Array(calls.iter().map(answer).collect()),
+                call => answer(&call),
+            };
+            (200, body.to_string())
+        })
+        .await
+    }
+
+    async fn quorum_of(results: &[&'static str], quorum: Quorum) -> (Vec<MockServer>, QuorumProvider<TestTarget>) {
+        let mut servers = Vec::new();
+        for result in results {
+            servers.push(node(result).await);
+        }
+        let provider = QuorumProvider::from_urls(servers.iter().map(MockServer::url), quorum);
+        (servers, provider)
+    }
+
+    #[tokio::test]
+    async fn test_majority_agreement() {
+        let (_servers, provider) = quorum_of(&["0x10", "0x10", "0x11"], Quorum::Majority).await;
+        let block: String = provider.call(block_number()).await.unwrap();
+        assert_eq!(block, "0x10");
+    }
+
+    #[tokio::test]
+    async fn test_disagreement_lists_every_answer() {
+        let (servers, provider) = quorum_of(&["0x10", "0x10", "0x11"], Quorum::All).await;
+        match provider.call::<String>(block_number()).await {
+            Err(QuorumError::NoAgreement { required, index, answers }) => {
+                assert_eq!(required, 3);
+                assert_eq!(index, None);
+                let answers = answers.into_iter().map(|a| (a.endpoint, a.answer.unwrap())).collect::<Vec<_>>();
+                assert_eq!(
+                    answers,
+                    vec![
+                        (servers[0].url(), Value::from("0x10")),
+                        (servers[1].url(), Value::from("0x10")),
+                        (servers[2].url(), Value::from("0x11")),
+                    ]
+                );
+            }
+            other => panic!("expected disagreement, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_equality() {
+        let (_servers, provider) = quorum_of(&["0xAB", "0xab"], Quorum::All).await;
+        let block: String = provider
+            .call_by(block_number(), |a: &String, b: &String| a.eq_ignore_ascii_case(b))
+            .await
+            .unwrap();
+        assert_eq!(block, "0xAB");
+    }
+
+    #[tokio::test]
+    async fn test_typed_comparison_reports_undeserializable_answers() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Block {
+            number: u64,
+        }
+
+        let node = |result: Value| {
+            test_util::serve(move |request| {
+                let body = serde_json::json!({ "jsonrpc": "2.0", "id": request.json()["id"], "result": result });
+                (200, body.to_string())
+            })
+        };
+        // the same block, serialized with different key order and an extra field
+        let a = node(serde_json::json!({ "number": 16, "hash": "0x01" })).await;
+        let b = node(serde_json::json!({ "hash": "0x01", "number": 16, "extra": true })).await;
+        let c = node(Value::from("0x10")).await;
+        let provider = QuorumProvider::from_urls([a.url(), b.url(), c.url()], Quorum::Majority);
+        let block: Block = provider.call(block_number()).await.unwrap();
+        assert_eq!(block, Block { number: 16 });
+
+        let provider = QuorumProvider::from_urls([a.url(), c.url()], Quorum::All);
+        match provider.call::<Block>(block_number()).await {
+            Err(QuorumError::NoAgreement { answers, .. }) => {
+                assert!(answers[0].answer.is_ok());
+                let error = answers[1].answer.as_ref().unwrap_err();
+                assert_eq!(error.code, -32603);
+                assert!(error.message.contains("Failed to deserialize"), "{}", error.message);
+            }
+            other => panic!("expected disagreement, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batch() {
+        let (servers, provider) = quorum_of(&["0x10", "0x10", "0x11"], Quorum::AtLeast(2)).await;
+        let blocks: Vec<String> = provider.batch(vec![block_number(), block_number()]).await.unwrap();
+        assert_eq!(blocks, vec!["0x10", "0x10"]);
+
+        let provider = QuorumProvider::from_urls(servers.iter().map(MockServer::url), Quorum::All);
+        let err = provider.batch::<String>(vec![block_number(), block_number()]).await.unwrap_err();
+        assert!(matches!(err, QuorumError::NoAgreement { index: Some(0), .. }));
+    }
+
+    #[tokio::test]
+    async fn test_batch_with_repeating_ids() {
+        use crate::jsonrpc::JsonRpcId;
+
+        let (a, b) = (node("0x10").await, node("0x10").await);
+        let member = |server: &MockServer| Provider::default().with_base_url(server.url()).with_id_generator(|| JsonRpcId::from("fixed"));
+        let provider = QuorumProvider::new(Quorum::All).with_member("a", member(&a)).with_member("b", member(&b));
+
+        let blocks: Vec<String> = provider.batch(vec![block_number(), block_number()]).await.unwrap();
+        assert_eq!(blocks, vec!["0x10", "0x10"]);
+    }
+}
@@ -0,0 +1,434 @@
+//! Server-side routing: parse incoming `http::Request`s back into the `Target` enum that a client
+//! would have sent, so one enum can describe both sides of an API.
+use crate::http::HTTPMethod;
+use bytes::Bytes;
+use percent_encoding::percent_decode_str;
+use serde::de::DeserializeOwned;
+use std::{cell::Cell, collections::HashMap, str::FromStr};
+use thiserror::Error;
+
+/// Reconstructs a target from an incoming request, the inverse of `Target::to_http_request`.
+///
+/// Implementations usually try each variant's method and path template in turn with a
+/// `RequestMatcher`:
+///
+/// ```rust,ignore
+/// impl FromHttpRequest for UserApi {
+///     fn from_http_request(request: &http::Request<Bytes>) -> Result<Self, RouteError> {
+///         let matcher = RequestMatcher::new(request);
+///         if let Some(params) = matcher.route(HTTPMethod::GET, "/users/{id}") {
+///             return Ok(UserApi::Get { id: params.get("id")? });
+///         }
+///         if matcher.route(HTTPMethod::POST, "/users").is_some() {
+///             return Ok(UserApi::Create(matcher.json()?));
+///         }
+///         Err(matcher.no_match())
+///     }
+/// }
+/// ```
+pub trait FromHttpRequest: Sized {
+    fn from_http_request(request: &http::Request<Bytes>) -> Result<Self, RouteError>;
+}
+
+#[derive(Debug, Error)]
+pub enum RouteError {
+    #[error("No route for {method} {path}")]
+    NotFound { method: http::Method, path: String },
+
+    /// The path matched a route, but not with this method.
+    #[error("Method {method} not allowed for {path}")]
+    MethodNotAllowed { method: http::Method, path: String },
+
+    #[error("Missing parameter {name}")]
+    MissingParam { name: String },
+
+    #[error("Invalid value {value:?} for parameter {name}")]
+    InvalidParam { name: String, value: String },
+
+    /// The query string does not percent-decode to UTF-8.
+    #[error("Invalid query string {query:?}")]
+    InvalidQuery { query: String },
+
+    #[error("Invalid request body: {0}")]
+    InvalidBody(#[from] serde_json::Error),
+}
+
+impl RouteError {
+    /// The HTTP status a server should answer with.
+    pub fn status(&self) -> http::StatusCode {
+        match self {
+            RouteError::NotFound { .. } => http::StatusCode::NOT_FOUND,
+            RouteError::MethodNotAllowed { .. } => http::StatusCode::METHOD_NOT_ALLOWED,
+            RouteError::MissingParam { .. }
+            | RouteError::InvalidParam { .. }
+            | RouteError::InvalidQuery { .. }
+            | RouteError::InvalidBody(_) => http::StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// Path or query parameters captured from a request, percent-decoded. A query key may carry
+/// several values, kept in request order.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params(HashMap<String, Vec<String>>);
+
+impl Params {
+    /// Parses the parameter `name`, e.g. `params.get::<u64>("id")`. With a repeated query key,
+    /// the first value is used; see `get_all`.
+    pub fn get<V: FromStr>(&self, name: &str) -> Result<V, RouteError> {
+        let value = self
+            .0
+            .get(name)
+            .and_then(|values| values.first())
+            .ok_or_else(|| RouteError::MissingParam { name: name.into() })?;
+        parse(name, value)
+    }
+
+    /// Parses every value of `name`, e.g. `?tag=a&tag=b`; empty if the parameter is absent.
+    pub fn get_all<V: FromStr>(&self, name: &str) -> Result<Vec<V>, RouteError> {
+        self.0.get(name).into_iter().flatten().map(|value| parse(name, value)).collect()
+    }
+
+    /// Like `get`, but `None` if the parameter is absent.
+    pub fn get_opt<V: FromStr>(&self, name: &str) -> Result<Option<V>, RouteError> {
+        match self.0.contains_key(name) {
+            true => self.get(name).map(Some),
+            false => Ok(None),
+        }
+    }
+
+    pub fn into_map(self) -> HashMap<String, Vec<String>> {
+        self.0
+    }
+
+    fn insert(&mut self, name: String, value: String) {
+        self.0.entry(name).or_default().push(value);
+    }
+}
+
+fn parse<V: FromStr>(name: &str, value: &str) -> Result<V, RouteError> {
+    value.parse().map_err(|_| RouteError::InvalidParam {
+        name: name.into(),
+        value: value.into(),
+    })
+}
+
+/// Matches a path against a template such as `/users/{id}/posts/{post_id}`, returning the captured
+/// segments. A final `{*rest}` segment captures the remainder of the path.
+pub fn match_path(template: &str, path: &str) -> Option<Params> {
+    let mut params = Params::default();
+    let mut segments = path.trim_start_matches('/').split('/');
+    let mut templates = template.trim_start_matches('/').split('/').peekable();
+
+    while let Some(expected) = templates.next() {
+        if let Some(name) = expected.strip_prefix("{*").and_then(|rest| rest.strip_suffix('}')) {
+            let rest = segments.by_ref().collect::<Vec<_>>().join("/");
+            params.insert(name.to_string(), decode(&rest));
+            return templates.peek().is_none().then_some(params);
+        }
+        let segment = segments.next()?;
+        match expected.strip_prefix('{').and_then(|rest| rest.strip_suffix('}')) {
+            Some(name) if !segment.is_empty() => {
+                params.insert(name.to_string(), decode(segment));
+            }
+            Some(_) => return None,
+            None if expected == segment => {}
+            None => return None,
+        }
+    }
+    segments.next().is_none().then_some(params)
+}
+
+fn decode(value: &str) -> String {
+    percent_decode_str(value).decode_utf8_lossy().into_owned()
+}
+
+/// Helper for `FromHttpRequest` implementations: matches routes and extracts the query and body.
+#[derive(Debug)]
+pub struct RequestMatcher<'a> {
+    request: &'a http::Request<Bytes>,
+    path_matched: Cell<bool>,
+}
+
+impl<'a> RequestMatcher<'a> {
+    pub fn new(request: &'a http::Request<Bytes>) -> Self {
+        Self {
+            request,
+            path_matched: Cell::new(false),
+        }
+    }
+
+    pub fn request(&self) -> &'a http::Request<Bytes> {
+        self.request
+    }
+
+    /// The path parameters if the request has `method` and its path matches `template`.
+    pub fn route(&self, method: HTTPMethod, template: &str) -> Option<Params> {
+        let params = match_path(template, self.request.uri().path())?;
+        if *self.request.method() != reqwest::Method::from(method) {
+            self.path_matched.set(true);
+            return None;
+        }
+        Some(params)
+    }
+
+    /// The decoded query parameters, keeping every value of a repeated key. Fails with
+    /// `InvalidQuery` if a key or value does not decode to UTF-8.
+    pub fn query(&self) -> Result<Params, RouteError> {
+        let query = self.request.uri().query().unwrap_or_default();
+        let decode = |part: &str| {
+            let part = part.replace('+', " ");
+            match percent_decode_str(&part).decode_utf8() {
+                Ok(part) => Ok(part.into_owned()),
+                Err(_) => Err(RouteError::InvalidQuery { query: query.into() }),
+            }
+        };
+        let mut params = Params::default();
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            params.insert(decode(name)?, decode(value)?);
+        }
+        Ok(params)
+    }
+
+    /// Deserializes the JSON body.
+    pub fn json<U: DeserializeOwned>(&self) -> Result<U, RouteError> {
+        Ok(serde_json::from_slice(self.request.body())?)
+    }
+
+    /// The error to return when no route matched: `MethodNotAllowed` if some route matched the
+    /// path with another method, `NotFound` otherwise.
+    pub fn no_match(&self) -> RouteError {
+        let (method, path) = (self.request.method().clone(), self.request.uri().path().to_string());
+        match self.path_matched.get() {
+            true => RouteError::MethodNotAllowed { method, path },
+            false => RouteError::NotFound { method, path },
+        }
+    }
+}
+
+#[cfg(feature = "axum")]
+mod axum {
+    use super::{FromHttpRequest, RouteError};
+    use axum_core::{
+        body::Body,
+        extract::{FromRequest, Request},
+        response::{IntoResponse, Response},
+    };
+    use http_body_util::BodyExt;
+
+    /// Axum extractor parsing the request into a `FromHttpRequest` target, so a handler can `match`
+    /// on the same enum its clients send. Rejects with the `RouteError`'s status.
+    ///
+    /// ```rust,ignore
+    /// async fn handler(Routed(target): Routed<UserApi>) -> impl IntoResponse {
+    ///     match target {
+    ///         UserApi::Get { id } => ...,
+    ///         UserApi::Create(user) => ...,
+    ///     }
+    /// }
+    /// ```
+    #[derive(Debug, Clone)]
+    pub struct Routed<T>(pub T);
+
+    impl<T, S> FromRequest<S> for Routed<T>
+    where
+        T: FromHttpRequest,
+        S: Send + Sync,
+    {
+        type Rejection = Response;
+
+        async fn from_request(request: Request, _state: &S) -> Result<Self, Response> {
+            let (parts, body) = request.into_parts();
+            let body = body
+                .collect()
+                .await
+                .map_err(|e| (http::StatusCode::BAD_REQUEST, e.to_string()).into_response())?
+                .to_bytes();
+            let request = http::Request::from_parts(parts, body);
+            T::from_http_request(&request).map(Routed).map_err(IntoResponse::into_response)
+        }
+    }
+
+    impl IntoResponse for RouteError {
+        fn into_response(self) -> Response {
+            let mut response = Response::new(Body::from(self.to_string()));
+            *response.status_mut() = self.status();
+            response
+        }
+    }
+}
+
+#[cfg(feature = "axum")]
+pub use self::axum::Routed;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Error,
+        http::{AuthMethod, HTTPBody},
+        target::Target,
+    };
+    use serde::{Deserialize, Serialize};
+    use std::borrow::Cow;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct User {
+        name: String,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum UserApi {
+        Get { id: u64 },
+        Search { name: String, page: Option<u32> },
+        Create(User),
+        File(String),
+    }
+
+    impl Target for UserApi {
+        fn base_url(&self) -> Cow<'_, str> {
+            Cow::Borrowed("http://users.internal")
+        }
+
+        fn method(&self) -> HTTPMethod {
+            match self {
+                UserApi::Create(_) => HTTPMethod::POST,
+                _ => HTTPMethod::GET,
+            }
+        }
+
+        fn path(&self) -> String {
+            match self {
+                UserApi::Get { id } => format!("/users/{}", id),
+                UserApi::Search { .. } | UserApi::Create(_) => "/users".into(),
+                UserApi::File(path) => format!("/files/{}", path),
+            }
+        }
+
+        fn query(&self) -> HashMap<String, String> {
+            match self {
+                UserApi::Search { name, page } => {
+                    let mut query = HashMap::from([("name".to_string(), name.clone())]);
+                    if let Some(page) = page {
+                        query.insert("page".into(), page.to_string());
+                    }
+                    query
+                }
+                _ => HashMap::default(),
+            }
+        }
+
+        fn headers(&self) -> HashMap<String, String> {
+            HashMap::default()
+        }
+
+        fn authentication(&self) -> Option<AuthMethod> {
+            None
+        }
+
+        fn body(&self) -> Result<HTTPBody, Error> {
+            match self {
+                UserApi::Create(user) => Ok(HTTPBody::from(user)?),
+                _ => Ok(HTTPBody::default()),
+            }
+        }
+    }
+
+    impl FromHttpRequest for UserApi {
+        fn from_http_request(request: &http::Request<Bytes>) -> Result<Self, RouteError> {
+            let matcher = RequestMatcher::new(request);
+            if let Some(params) = matcher.route(HTTPMethod::GET, "/users/{id}") {
+                return Ok(UserApi::Get { id: params.get("id")? });
+            }
+            if matcher.route(HTTPMethod::GET, "/users").is_some() {
+                let query = matcher.query()?;
+                return Ok(UserApi::Search {
+                    name: query.get("name")?,
+                    page: query.get_opt("page")?,
+                });
+            }
+            if matcher.route(HTTPMethod::POST, "/users").is_some() {
+                return Ok(UserApi::Create(matcher.json()?));
+            }
+            if let Some(params) = matcher.route(HTTPMethod::GET, "/files/{*path}") {
+                return Ok(UserApi::File(params.get("path")?));
+            }
+            Err(matcher.no_match())
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let targets = vec![
+            UserApi::Get { id: 42 },
+            UserApi::Search { name: "Ada Lovelace".into(), page: Some(2) },
+            UserApi::Search { name: "grace".into(), page: None },
+            UserApi::Create(User { name: "Alan".into() }),
+            UserApi::File("docs/readme.md".into()),
+        ];
+        for target in targets {
+            let request = target.to_http_request().unwrap();
+            assert_eq!(UserApi::from_http_request(&request).unwrap(), target);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let request = |method: &str, uri: &str| http::Request::builder().method(method).uri(uri).body(Bytes::new()).unwrap();
+
+        let err = UserApi::from_http_request(&request("DELETE", "/users/1")).unwrap_err();
+        assert_eq!(err.status(), http::StatusCode::METHOD_NOT_ALLOWED);
+        let err = UserApi::from_http_request(&request("GET", "/teams")).unwrap_err();
+        assert_eq!(err.status(), http::StatusCode::NOT_FOUND);
+        let err = UserApi::from_http_request(&request("GET", "/users/abc")).unwrap_err();
+        assert!(matches!(err, RouteError::InvalidParam { ref name, .. } if name == "id"));
+        let err = UserApi::from_http_request(&request("POST", "/users")).unwrap_err();
+        assert_eq!(err.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_query() {
+        let request = |uri: &str| http::Request::get(uri).body(Bytes::new()).unwrap();
+
+        let request = request("/users?tag=a&tag=b+c&page=2&flag");
+        let query = RequestMatcher::new(&request).query().unwrap();
+        assert_eq!(query.get_all::<String>("tag").unwrap(), vec!["a", "b c"]);
+        assert_eq!(query.get::<String>("tag").unwrap(), "a");
+        assert_eq!(query.get_all::<u32>("page").unwrap(), vec![2]);
+        assert_eq!(query.get::<String>("flag").unwrap(), "");
+        assert!(query.get_all::<String>("missing").unwrap().is_empty());
+
+        let request = http::Request::get("/users?name=%FF").body(Bytes::new()).unwrap();
+        let err = RequestMatcher::new(&request).query().unwrap_err();
+        assert!(matches!(err, RouteError::InvalidQuery { .. }));
+        assert_eq!(err.status(), http::StatusCode::BAD_REQUEST);
+        let err = UserApi::from_http_request(&request).unwrap_err();
+        assert_eq!(err.status(), http::StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn test_match_path() {
+        assert_eq!(match_path("/a/{x}", "/a/b%20c").unwrap().get::<String>("x").unwrap(), "b c");
+        assert!(match_path("/a/{x}", "/a/").is_none());
+        assert!(match_path("/a/{x}", "/a/b/c").is_none());
+        assert!(match_path("/a", "/a/b").is_none());
+        assert_eq!(match_path("/a/{*rest}", "/a/b/c").unwrap().get::<String>("rest").unwrap(), "b/c");
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn test_axum_extractor() {
+        use axum_core::extract::FromRequest;
+
+        let request = http::Request::post("/users")
+            .body(axum_core::body::Body::from(r#"{"name":"Alan"}"#))
+            .unwrap();
+        let Routed(target) = Routed::<UserApi>::from_request(request, &()).await.unwrap();
+        assert_eq!(target, UserApi::Create(User { name: "Alan".into() }));
+
+        let request = http::Request::get("/nope").body(axum_core::body::Body::empty()).unwrap();
+        let rejection = Routed::<UserApi>::from_request(request, &()).await.unwrap_err();
+        assert_eq!(rejection.status(), http::StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,201 @@
+//! `tower` integration (via the `tower` feature): use a `Provider` as a `tower::Service`, and send
+//! a provider's requests through any `Service<http::Request<Bytes>>`.
+use crate::{
+    error::Error,
+    http::HTTPResponse,
+    provider::{Provider, ProviderType},
+    target::Target,
+    transport::{Body, DefaultTransport, Transport},
+};
+use bytes::Bytes;
+use futures::future::BoxFuture;
+use std::{
+    future::poll_fn,
+    sync::Arc,
+    task::{Context, Poll},
+};
+use tower::{BoxError, Service};
+
+#[cfg(feature = "jsonrpc")]
+use crate::{
+    jsonrpc::{JsonRpcError, JsonRpcResult},
+    provider::JsonRpcProviderType,
+    target::JsonRpcTarget,
+};
+
+/// A cloneable `tower::Service` over a shared `Provider`, created with `Provider::into_service`.
+///
+/// `Service<T>` sends one target and returns the raw response; with the `jsonrpc` feature,
+/// `Service<Vec<T>>` sends the targets as one JSON-RPC batch. Wrap it in any tower layer:
+///
+/// ```rust,ignore
+/// let service = ServiceBuilder::new()
+///     .timeout(Duration::from_secs(5))
+///     .service(Provider::<HttpBin>::default().into_service());
+/// let response = service.oneshot(HttpBin::Get).await?;
+/// ```
+pub struct ProviderService<T: Target, X: Transport = DefaultTransport> {
+    provider: Arc<Provider<T, X>>,
+}
+
+impl<T: Target, X: Transport> Clone for ProviderService<T, X> {
+    fn clone(&self) -> Self {
+        Self {
+            provider: self.provider.clone(),
+        }
+    }
+}
+
+impl<T: Target, X: Transport> std::fmt::Debug for ProviderService<T, X> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProviderService").field("provider", &self.provider).finish()
+    }
+}
+
+impl<T: Target, X: Transport> ProviderService<T, X> {
+    pub fn new(provider: Provider<T, X>) -> Self {
+        Self {
+            provider: Arc::new(provider),
+        }
+    }
+
+    pub fn provider(&self) -> &Provider<T, X> {
+        &self.provider
+    }
+}
+
+impl<T, X> Service<T> for ProviderService<T, X>
+where
+    T: Target + Send + Sync + 'static,
+    X: Transport,
+{
+    type Response = HTTPResponse;
+    type Error = Error;
+    type Future = BoxFuture<'static, Result<HTTPResponse, Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        let provider = self.provider.clone();
+        Box::pin(async move { provider.request(target).await })
+    }
+}
+
+#[cfg(feature = "jsonrpc")]
+impl<T, X> Service<Vec<T>> for ProviderService<T, X>
+where
+    T: JsonRpcTarget + Send + Sync + 'static,
+    X: Transport,
+{
+    type Response = Vec<JsonRpcResult<serde_json::Value>>;
+    type Error = JsonRpcError;
+    type Future = BoxFuture<'static, Result<Self::Response, JsonRpcError>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), JsonRpcError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, targets: Vec<T>) -> Self::Future {
+        let provider = self.provider.clone();
+        Box::pin(async move { provider.batch(targets).await })
+    }
+}
+
+/// A `Transport` backed by a `tower::Service`, so a provider's requests go through a tower stack
+/// (load shedding, concurrency limits, custom clients) before reaching the network.
+/// The service is cloned for each request, as tower services usually are.
+#[derive(Debug, Clone)]
+pub struct TowerTransport<S> {
+    service: S,
+}
+
+impl<S> TowerTransport<S> {
+    pub fn new(service: S) -> Self {
+        Self { service }
+    }
+}
+
+impl<S, B> Transport for TowerTransport<S>
+where
+    S: Service<http::Request<Bytes>, Response = http::Response<B>> + Clone + Send + Sync + 'static,
+    S::Future: Send,
+    S::Error: Into<BoxError>,
+    B: Into<Body>,
+{
+    async fn execute(&self, request: http::Request<Bytes>) -> Result<http::Response<Body>, Error> {
+        let mut service = self.service.clone();
+        poll_fn(|cx| service.poll_ready(cx)).await.map_err(|e| Error::Transport(e.into()))?;
+        let response = service.call(request).await.map_err(|e| Error::Transport(e.into()))?;
+        Ok(response.map(Into::into))
+    }
+}
+
+impl<T: Target, X: Transport> Provider<T, X> {
+    /// Shares this provider as a cloneable `tower::Service`, see `ProviderService`.
+    pub fn into_service(self) -> ProviderService<T, X> {
+        ProviderService::new(self)
+    }
+
+    /// Sends requests through `service`, e.g. a stack built with `tower::ServiceBuilder`.
+    pub fn with_service<S>(self, service: S) -> Provider<T, TowerTransport<S>>
+    where
+        TowerTransport<S>: Transport,
+    {
+        self.with_transport(TowerTransport::new(service))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestTarget;
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+    use tower::{ServiceBuilder, ServiceExt, service_fn};
+
+    fn status(code: u16) -> TestTarget {
+        TestTarget::get("http://status.test", format!("/status/{}", code))
+    }
+
+    /// Answers with the status code in the request path.
+    fn status_response(request: &http::Request<Bytes>) -> http::Response<Bytes> {
+        let status = request.uri().path().trim_start_matches("/status/").parse::<u16>().unwrap();
+        let mut response = http::Response::new(Bytes::new());
+        *response.status_mut() = http::StatusCode::from_u16(status).unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_provider_as_service_behind_layers() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let inner = service_fn(move |request: http::Request<Bytes>| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            std::future::ready(Ok::<_, Infallible>(status_response(&request)))
+        });
+        let provider = Provider::<TestTarget>::default().with_service(inner);
+        let service = ServiceBuilder::new()
+            .timeout(Duration::from_secs(1))
+            .service(provider.into_service());
+
+        let response = service.clone().oneshot(status(204)).await.unwrap();
+        assert_eq!(response.status(), 204);
+        let response = service.oneshot(status(503)).await.unwrap();
+        assert_eq!(response.status(), 503);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_tower_transport_errors() {
+        let failing = service_fn(|_: http::Request<Bytes>| async { Err::<http::Response<Bytes>, _>("unreachable") });
+        let provider = Provider::<TestTarget>::default().with_service(failing);
+        let err = provider.request(status(200)).await.unwrap_err();
+        assert!(matches!(err, Error::Transport(_)));
+        assert_eq!(err.to_string(), "Transport error: unreachable");
+    }
+}
@@ -0,0 +1,235 @@
+//! Golden snapshots of built requests (via the `testing` feature), to catch unintended wire format
+//! changes when refactoring targets. See `assert_request_snapshot!`.
+use crate::{error::Error, provider::Provider, target::Target, transport::Transport};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Set to update snapshots instead of comparing against them.
+pub const UPDATE_SNAPSHOTS_ENV: &str = "REQWEST_ENUM_UPDATE_SNAPSHOTS";
+
+/// Header name fragments whose values are replaced by `[redacted]` in snapshots.
+const SECRET_HEADER_HINTS: [&str; 7] = ["auth", "token", "secret", "key", "cookie", "password", "signature"];
+
+/// Asserts that the request `provider` builds for `target` matches its snapshot in
+/// `$CARGO_MANIFEST_DIR/snapshots`.
+///
+/// The snapshot is named after the calling test (numbered if a test takes several), or given
+/// explicitly as a third argument. Missing or outdated snapshots fail the test; run with
+/// `REQWEST_ENUM_UPDATE_SNAPSHOTS=1` to write them, then review the diff.
+///
+/// ```rust,ignore
+/// #[test]
+/// fn get_request() {
+///     let provider = Provider::<HttpBin>::default();
+///     assert_request_snapshot!(provider, HttpBin::Get);
+///     assert_request_snapshot!(provider, HttpBin::Post, "httpbin_post");
+/// }
+/// ```
+#[macro_export]
+macro_rules! assert_request_snapshot {
+    ($provider:expr, $target:expr $(,)?) => {{
+        fn here() {}
+        let name = $crate::snapshot::snapshot_name(::std::any::type_name_of_val(&here));
+        $crate::assert_request_snapshot!($provider, $target, &name)
+    }};
+    ($provider:expr, $target:expr, $name:expr $(,)?) => {{
+        let rendered = $crate::snapshot::render_request(&$provider, &$target).expect("failed to build the request");
+        $crate::snapshot::assert_snapshot(
+            &::std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots"),
+            $name,
+            &rendered,
+        );
+    }};
+}
+
+/// Renders the request `provider` builds for `target` in a stable text form: method and URL with
+/// sorted query, sorted headers with secrets redacted, and the body (pretty-printed if JSON).
+pub fn render_request<T: Target, X: Transport>(provider: &Provider<T, X>, target: &T) -> Result<String, Error> {
+    let request = provider.build_request(target)?;
+    let uri = request.uri();
+
+    let mut url = format!(
+        "{}://{}{}",
+        uri.scheme_str().unwrap_or("http"),
+        uri.authority().map_or("", |authority| authority.as_str()),
+        uri.path()
+    );
+    if let Some(query) = uri.query() {
+        let mut pairs = serde_urlencoded::from_str::<Vec<(String, String)>>(query).unwrap_or_default();
+        pairs.sort();
+        url.push('?');
+        url.push_str(&serde_urlencoded::to_string(pairs).unwrap_or_default());
+    }
+
+    let mut headers = request
+        .headers()
+        .iter()
+        .map(|(name, value)| {
+            let name = name.as_str();
+            let value = if value.is_sensitive() || SECRET_HEADER_HINTS.iter().any(|hint| name.contains(hint)) {
+                "[redacted]".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            format!("{}: {}", name, value)
+        })
+        .collect::<Vec<_>>();
+    headers.sort();
+
+    let mut rendered = format!("{} {}\n", request.method(), url);
+    for header in headers {
+        rendered.push_str(&header);
+        rendered.push('\n');
+    }
+
+    let body = request.body();
+    if !body.is_empty() {
+        rendered.push('\n');
+        match serde_json::from_slice::<serde_json::Value>(body) {
+            Ok(json) => rendered.push_str(&serde_json::to_string_pretty(&json)?),
+            Err(_) => match std::str::from_utf8(body) {
+                Ok(text) => rendered.push_str(text),
+                Err(_) => rendered.push_str(&format!("<{} bytes>", body.len())),
+            },
+        }
+        rendered.push('\n');
+    }
+    Ok(rendered)
+}
+
+/// Turns the path of a function inside a test into a snapshot name, e.g.
+/// `my_crate::api::tests::get_user::here` into `api__tests__get_user`. Repeated calls from the
+/// same test are numbered (`api__tests__get_user-2`).
+pub fn snapshot_name(function_path: &str) -> String {
+    static CALLS: Mutex<Option<HashMap<String, usize>>> = Mutex::new(None);
+
+    let segments = function_path
+        .split("::")
+        .filter(|segment| !segment.starts_with('{'))
+        .collect::<Vec<_>>();
+    let segments = &segments[1.min(segments.len())..segments.len().saturating_sub(1)];
+    let name = segments.join("__");
+
+    let mut calls = CALLS.lock().unwrap();
+    let count = calls.get_or_insert_with(HashMap::new).entry(name.clone()).or_default();
+    *count += 1;
+    match *count {
+        1 => name,
+        n => format!("{}-{}", name, n),
+    }
+}
+
+/// Compares `rendered` with the snapshot `name` in `dir`, or writes it when
+/// `REQWEST_ENUM_UPDATE_SNAPSHOTS` is set. Panics with a line diff on mismatch.
+pub fn assert_snapshot(dir: &Path, name: &str, rendered: &str) {
+    let update = std::env::var_os(UPDATE_SNAPSHOTS_ENV).is_some_and(|value| !value.is_empty() && value != "0");
+    if let Err(message) = check_snapshot(&snapshot_path(dir, name), rendered, update) {
+        panic!("{}", message);
+    }
+}
+
+fn snapshot_path(dir: &Path, name: &str) -> PathBuf {
+    dir.join(format!("{}.snap", name))
+}
+
+fn check_snapshot(path: &Path, rendered: &str, update: bool) -> Result<(), String> {
+    if update {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {}", dir.display(), e))?;
+        }
+        return std::fs::write(path, rendered).map_err(|e| format!("failed to write {}: {}", path.display(), e));
+    }
+
+    let Ok(expected) = std::fs::read_to_string(path) else {
+        return Err(format!(
+            "missing request snapshot {}, run with {}=1 to create it:\n{}",
+            path.display(),
+            UPDATE_SNAPSHOTS_ENV,
+            rendered
+        ));
+    };
+    if expected == rendered {
+        return Ok(());
+    }
+
+    let expected_lines = expected.lines().collect::<Vec<_>>();
+    let rendered_lines = rendered.lines().collect::<Vec<_>>();
+    let mut diff = String::new();
+    for i in 0..expected_lines.len().max(rendered_lines.len()) {
+        match (expected_lines.get(i), rendered_lines.get(i)) {
+            (Some(old), Some(new)) if old == new => diff.push_str(&format!("  {}\n", old)),
+            (old, new) => {
+                if let Some(old) = old {
+                    diff.push_str(&format!("- {}\n", old));
+                }
+                if let Some(new) = new {
+                    diff.push_str(&format!("+ {}\n", new));
+                }
+            }
+        }
+    }
+    Err(format!(
+        "request snapshot {} changed, run with {}=1 to accept:\n{}",
+        path.display(),
+        UPDATE_SNAPSHOTS_ENV,
+        diff
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestTarget;
+    use serde_json::json;
+
+    fn create_user(name: &str) -> TestTarget {
+        TestTarget::post("https://api.example", "/users")
+            .with_query("z", "last")
+            .with_query("a", "first value")
+            .with_header("X-Request-Source", "tests")
+            .with_header("X-Api-Key", "abc123")
+            .with_bearer("secret-token")
+            .with_json(json!({ "name": name, "admin": false }))
+    }
+
+    #[test]
+    fn test_render_request() {
+        let rendered = render_request(&Provider::<TestTarget>::default(), &create_user("ada")).unwrap();
+        assert_eq!(
+            rendered,
+            "POST https://api.example/users?a=first+value&z=last\n\
+             authorization: [redacted]\n\
+             x-api-key: [redacted]\n\
+             x-request-source: tests\n\
+             \n\
+             {\n  \"admin\": false,\n  \"name\": \"ada\"\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_check_snapshot() {
+        let path = std::env::temp_dir().join(format!("reqwest-enum-snapshot-{}.snap", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let err = check_snapshot(&path, "GET /a\n", false).unwrap_err();
+        assert!(err.starts_with("missing request snapshot"));
+        check_snapshot(&path, "GET /a\n", true).unwrap();
+        check_snapshot(&path, "GET /a\n", false).unwrap();
+
+        let err = check_snapshot(&path, "GET /b\n", false).unwrap_err();
+        assert!(err.ends_with("- GET /a\n+ GET /b\n"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_assert_request_snapshot() {
+        let provider = Provider::<TestTarget>::default();
+        crate::assert_request_snapshot!(provider, create_user("grace"));
+        crate::assert_request_snapshot!(provider, create_user("grace"), "create_user_grace");
+        assert_eq!(snapshot_name("my_crate::api::tests::get_user::{{closure}}::here"), "api__tests__get_user");
+        assert_eq!(snapshot_name("my_crate::api::tests::get_user::{{closure}}::here"), "api__tests__get_user-2");
+    }
+}
@@ -1,7 +1,12 @@
 use crate::{
     Error,
     http::{AuthMethod, HTTPBody, HTTPMethod},
+    provider::{shared_builder_client, target_request_builder},
+    query::QueryParams,
+    url::build_url,
+    transport::from_reqwest_request,
 };
+use bytes::Bytes;
 use std::collections::HashMap;
 use std::borrow::Cow;
 
@@ -15,26 +20,45 @@ pub trait Target {
     fn authentication(&self) -> Option<AuthMethod>;
     fn body(&self) -> Result<HTTPBody, Error>;
 
+    /// The query parameters in the order they are sent. Defaults to `query()` sorted by key;
+    /// override to keep insertion order or serialize a struct, see `QueryParams`.
+    fn query_params(&self) -> QueryParams {
+        QueryParams::from(self.query())
+    }
+
     // helpers for url
+    /// The percent-encoded query string, without the leading `?`.
     fn query_string(&self) -> String {
-        self.query()
-            .iter()
-            .map(|(k, v)| format!("{}={}", k, v))
-            .collect::<Vec<String>>()
-            .join("&")
+        self.query_params().to_query_string()
+    }
+
+    /// The URL a default `Provider` sends this target to, including the query string,
+    /// see `url::build_url`.
+    fn absolute_url(&self) -> Result<reqwest::Url, Error> {
+        build_url(&self.base_url(), &self.path(), &self.query_string())
     }
 
-    fn absolute_url(&self) -> String {
-        let mut url = format!("{}{}", self.base_url(), self.path());
-        if !self.query_string().is_empty() {
-            url = format!("{}?{}", url, self.query_string());
-        }
-        url
+    /// Converts this target into an `http::Request`, exactly as a default `Provider` would send it
+    /// (URL, query, headers, authentication and body), for use with other HTTP stacks.
+    fn to_http_request(&self) -> Result<http::Request<Bytes>, Error> {
+        let request = target_request_builder(shared_builder_client(), self.absolute_url()?, self)?.build()?;
+        from_reqwest_request(request)
     }
 }
 
+/// Default idempotency check of `HedgePolicy` and `FailoverProvider`: GET, HEAD and OPTIONS.
+#[cfg(any(feature = "hedge", feature = "failover"))]
+pub(crate) fn is_safe_method<T: Target>(target: &T) -> bool {
+    matches!(target.method(), HTTPMethod::GET | HTTPMethod::HEAD | HTTPMethod::OPTIONS)
+}
+
 #[cfg(feature = "jsonrpc")]
 pub trait JsonRpcTarget: Target {
-    fn method_name(&self) -> &'static str;
+    /// The JSON-RPC method, either a `'static` literal (`"eth_call".into()`) or computed at runtime.
+    fn method_name(&self) -> Cow<'static, str>;
     fn params(&self) -> Vec<serde_json::Value>;
+    /// Whether this target is a notification (sent without an id, no response expected).
+    fn is_notification(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,170 @@
+//! Helpers shared by the crate's unit tests: a scripted backend on top of `MockServer` and one
+//! configurable `Target`.
+//! Not every helper is used under every feature combination.
+#![allow(dead_code)]
+use crate::{
+    error::Error,
+    http::{AuthMethod, HTTPBody, HTTPMethod},
+    target::Target,
+    testing::MockServer,
+};
+use bytes::Bytes;
+use serde_json::Value;
+use std::{borrow::Cow, collections::HashMap};
+
+/// Starts a `MockServer` answering every request with `handler`'s status and JSON body.
+pub(crate) async fn serve<F>(handler: F) -> MockServer
+where
+    F: Fn(&http::Request<Bytes>) -> (u16, String) + Send + Sync + 'static,
+{
+    let server = MockServer::start().await;
+    server.respond_with(move |request| {
+        let (status, body) = handler(request);
+        let mut response = http::Response::new(Bytes::from(body));
+        *response.status_mut() = http::StatusCode::from_u16(status).unwrap();
+        response
+            .headers_mut()
+            .insert(http::header::CONTENT_TYPE, http::HeaderValue::from_static("application/json"));
+        response
+    });
+    server
+}
+
+pub(crate) trait JsonBody {
+    fn json(&self) -> Value;
+}
+
+impl JsonBody for http::Request<Bytes> {
+    fn json(&self) -> Value {
+        serde_json::from_slice(self.body()).expect("request body is not JSON")
+    }
+}
+
+/// A target whose method, path, query, headers, bearer token, JSON body and JSON-RPC call are all
+/// chosen by the test.
+#[derive(Debug, Clone)]
+pub(crate) struct TestTarget {
+    base_url: String,
+    method: HTTPMethod,
+    path: String,
+    query: HashMap<String, String>,
+    headers: HashMap<String, String>,
+    bearer: Option<&'static str>,
+    body: Option<Value>,
+    rpc_method: &'static str,
+    params: Vec<Value>,
+    #[cfg(feature = "jsonrpc")]
+    notification: bool,
+}
+
+impl TestTarget {
+    pub fn new(method: HTTPMethod, base_url: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            method,
+            path: path.into(),
+            query: HashMap::default(),
+            headers: HashMap::default(),
+            bearer: None,
+            body: None,
+            rpc_method: "test",
+            params: Vec::new(),
+            #[cfg(feature = "jsonrpc")]
+            notification: false,
+        }
+    }
+
+    pub fn get(base_url: impl Into<String>, path: impl Into<String>) -> Self {
+        Self::new(HTTPMethod::GET, base_url, path)
+    }
+
+    pub fn post(base_url: impl Into<String>, path: impl Into<String>) -> Self {
+        Self::new(HTTPMethod::POST, base_url, path)
+    }
+
+    /// A JSON-RPC call posted to `base_url`.
+    #[cfg(feature = "jsonrpc")]
+    pub fn rpc(base_url: impl Into<String>, method: &'static str, params: Vec<Value>) -> Self {
+        Self::post(base_url, "/").with_rpc(method, params)
+    }
+
+    pub fn with_query(mut self, name: &str, value: &str) -> Self {
+        self.query.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn with_bearer(mut self, token: &'static str) -> Self {
+        self.bearer = Some(token);
+        self
+    }
+
+    pub fn with_json(mut self, body: Value) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn with_rpc(mut self, method: &'static str, params: Vec<Value>) -> Self {
+        self.rpc_method = method;
+        self.params = params;
+        self
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    pub fn notification(mut self) -> Self {
+        self.notification = true;
+        self
+    }
+}
+
+impl Target for TestTarget {
+    fn base_url(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.base_url)
+    }
+
+    fn method(&self) -> HTTPMethod {
+        self.method
+    }
+
+    fn path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn query(&self) -> HashMap<String, String> {
+        self.query.clone()
+    }
+
+    fn headers(&self) -> HashMap<String, String> {
+        self.headers.clone()
+    }
+
+    fn authentication(&self) -> Option<AuthMethod> {
+        self.bearer.map(|token| AuthMethod::Bearer(token.into()))
+    }
+
+    fn body(&self) -> Result<HTTPBody, Error> {
+        match &self.body {
+            Some(body) => Ok(HTTPBody::from(body)?),
+            None => Ok(HTTPBody::default()),
+        }
+    }
+}
+
+#[cfg(feature = "jsonrpc")]
+impl crate::target::JsonRpcTarget for TestTarget {
+    fn method_name(&self) -> Cow<'static, str> {
+        Cow::Borrowed(self.rpc_method)
+    }
+
+    fn params(&self) -> Vec<Value> {
+        self.params.clone()
+    }
+
+    fn is_notification(&self) -> bool {
+        self.notification
+    }
+}
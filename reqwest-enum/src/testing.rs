@@ -0,0 +1,638 @@
+//! In-process mock HTTP server (via the `testing` feature) with expectations keyed by `Target` values,
+//! so tests run offline against the same targets the application sends.
+use crate::{
+    provider::Provider,
+    target::Target,
+    transport::copy_request,
+};
+use bytes::Bytes;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+
+#[cfg(feature = "jsonrpc")]
+use crate::{
+    jsonrpc::{JsonRpcError, JsonRpcErrorResponse, JsonRpcId, JsonRpcResponse, JsonRpcResult},
+    target::JsonRpcTarget,
+};
+#[cfg(feature = "jsonrpc")]
+use serde_json::Value;
+
+/// A local HTTP server answering requests from registered mocks.
+///
+/// Requests are matched against mocks in registration order; a mock limited with `Mock::times`
+/// stops matching once it was called that often, so later mocks for the same target answer
+/// subsequent requests. Unmatched requests get a `404`, unless a `respond_with` fallback answers
+/// them. Dropping the server verifies every
+/// `times` expectation, like `verify`.
+///
+/// ```rust,ignore
+/// let server = MockServer::start().await;
+/// let mock = server.expect(HttpBin::Get).respond_json(json!({ "ok": true })).times(1);
+/// let provider: Provider<HttpBin> = server.provider();
+/// provider.request(HttpBin::Get).await?;
+/// assert_eq!(mock.requests()[0].uri().path(), "/get");
+/// ```
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<State>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct State {
+    mocks: Mutex<Vec<Arc<MockEntry>>>,
+    requests: Mutex<Vec<http::Request<Bytes>>>,
+}
+
+impl std::fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockServer")
+            .field("addr", &self.addr)
+            .field("mocks", &self.state.mocks.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl MockServer {
+    /// Binds to a random localhost port and starts serving on the current tokio runtime.
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("failed to bind mock server");
+        let addr = listener.local_addr().expect("mock server has no local address");
+        let state = Arc::new(State::default());
+
+        let serving = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, serving.clone()));
+            }
+        });
+
+        Self { addr, state, task }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// A provider sending every target to this server, see `Provider::with_base_url`.
+    pub fn provider<T: Target>(&self) -> Provider<T> {
+        Provider::default().with_base_url(self.url())
+    }
+
+    /// Expects `target` as the provider would send it: same method, path, query and, unless the
+    /// target has no body, an equal body (compared as JSON when both sides are JSON).
+    pub fn expect<T: Target>(&self, target: T) -> MockBuilder<'_> {
+        let request = target.to_http_request().expect("failed to build the expected request");
+        let base_path = target
+            .base_url()
+            .parse::<http::Uri>()
+            .map(|uri| uri.path().trim_end_matches('/').to_string())
+            .unwrap_or_default();
+        let path = request.uri().path();
+        MockBuilder {
+            server: self,
+            matcher: Matcher::Http {
+                method: request.method().clone(),
+                path: path.strip_prefix(base_path.as_str()).unwrap_or(path).to_string(),
+                query: query_pairs(request.uri().query()),
+                body: request.body().clone(),
+            },
+        }
+    }
+
+    /// Expects a JSON-RPC call of `target`'s method and params, single or within a batch.
+    /// Each call is answered with the request's id, so it works with any `IdGenerator`.
+    #[cfg(feature = "jsonrpc")]
+    pub fn expect_rpc<T: JsonRpcTarget>(&self, target: T) -> RpcMockBuilder<'_> {
+        RpcMockBuilder {
+            server: self,
+            method: target.method_name().into_owned(),
+            params: target.params(),
+        }
+    }
+
+    /// Answers every request no earlier `expect` mock matched with `responder`, e.g. to simulate a
+    /// backend with custom logic. The responder runs on tokio's blocking pool, so it may sleep.
+    pub fn respond_with<F>(&self, responder: F) -> Mock
+    where
+        F: Fn(&http::Request<Bytes>) -> http::Response<Bytes> + Send + Sync + 'static,
+    {
+        self.register(Matcher::Any, Answer::Fn(Responder(Box::new(responder))))
+    }
+
+    /// Every request received so far, oldest first, matched or not.
+    pub fn received_requests(&self) -> Vec<http::Request<Bytes>> {
+        self.state.requests.lock().unwrap().iter().map(copy_request).collect()
+    }
+
+    /// Panics if a mock limited with `Mock::times` was not called exactly that often.
+    pub fn verify(&self) {
+        let failures = self
+            .state
+            .mocks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|mock| {
+                let expected = (*mock.expected.lock().unwrap())?;
+                let calls = mock.calls();
+                (calls != expected).then(|| format!("{}: expected {} call(s), received {}", mock.matcher, expected, calls))
+            })
+            .collect::<Vec<_>>();
+        if !failures.is_empty() {
+            panic!("mock server expectations not met:\n{}", failures.join("\n"));
+        }
+    }
+
+    fn register(&self, matcher: Matcher, answer: Answer) -> Mock {
+        let entry = Arc::new(MockEntry {
+            matcher,
+            answer,
+            expected: Mutex::new(None),
+            requests: Mutex::default(),
+        });
+        self.state.mocks.lock().unwrap().push(entry.clone());
+        Mock { entry }
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+        if !std::thread::panicking() {
+            self.verify();
+        }
+    }
+}
+
+/// Chooses the response for an expectation created with `MockServer::expect`.
+#[derive(Debug)]
+pub struct MockBuilder<'a> {
+    server: &'a MockServer,
+    matcher: Matcher,
+}
+
+impl MockBuilder<'_> {
+    pub fn respond(self, response: http::Response<Bytes>) -> Mock {
+        let (parts, body) = response.into_parts();
+        self.server.register(self.matcher, Answer::Http(parts.status, parts.headers, body))
+    }
+
+    /// Responds `200` with `body` serialized as JSON.
+    pub fn respond_json<B: serde::Serialize>(self, body: B) -> Mock {
+        let body = serde_json::to_vec(&body).expect("failed to serialize mock response");
+        let mut response = http::Response::new(Bytes::from(body));
+        response
+            .headers_mut()
+            .insert(http::header::CONTENT_TYPE, http::HeaderValue::from_static("application/json"));
+        self.respond(response)
+    }
+
+    /// Responds with `status` and an empty body.
+    pub fn respond_status(self, status: u16) -> Mock {
+        let mut response = http::Response::new(Bytes::new());
+        *response.status_mut() = http::StatusCode::from_u16(status).expect("invalid status code");
+        self.respond(response)
+    }
+}
+
+/// Chooses the answer for an expectation created with `MockServer::expect_rpc`.
+#[cfg(feature = "jsonrpc")]
+#[derive(Debug)]
+pub struct RpcMockBuilder<'a> {
+    server: &'a MockServer,
+    method: String,
+    params: Vec<Value>,
+}
+
+#[cfg(feature = "jsonrpc")]
+impl RpcMockBuilder<'_> {
+    pub fn respond_result<R: serde::Serialize>(self, result: R) -> Mock {
+        let result = serde_json::to_value(result).expect("failed to serialize mock result");
+        self.answer(Ok(result))
+    }
+
+    pub fn respond_error(self, error: JsonRpcError) -> Mock {
+        self.answer(Err(error))
+    }
+
+    fn answer(self, answer: Result<Value, JsonRpcError>) -> Mock {
+        let matcher = Matcher::Rpc {
+            method: self.method,
+            params: self.params,
+        };
+        self.server.register(matcher, Answer::Rpc(answer))
+    }
+}
+
+/// A registered expectation; inspect what it received or limit how often it answers.
+#[derive(Debug, Clone)]
+pub struct Mock {
+    entry: Arc<MockEntry>,
+}
+
+impl Mock {
+    /// Answers at most `times` requests, and makes `MockServer::verify` require exactly that many.
+    pub fn times(self, times: usize) -> Self {
+        *self.entry.expected.lock().unwrap() = Some(times);
+        self
+    }
+
+    pub fn calls(&self) -> usize {
+        self.entry.calls()
+    }
+
+    /// The requests this mock answered, oldest first; a batch is recorded once per matching call.
+    pub fn requests(&self) -> Vec<http::Request<Bytes>> {
+        self.entry.requests.lock().unwrap().iter().map(copy_request).collect()
+    }
+}
+
+#[derive(Debug)]
+struct MockEntry {
+    matcher: Matcher,
+    answer: Answer,
+    expected: Mutex<Option<usize>>,
+    requests: Mutex<Vec<http::Request<Bytes>>>,
+}
+
+impl MockEntry {
+    fn calls(&self) -> usize {
+        self.requests.lock().unwrap().len()
+    }
+
+    fn exhausted(&self) -> bool {
+        matches!(*self.expected.lock().unwrap(), Some(expected) if self.calls() >= expected)
+    }
+
+    fn record(&self, request: &http::Request<Bytes>) {
+        self.requests.lock().unwrap().push(copy_request(request));
+    }
+}
+
+#[derive(Debug)]
+enum Matcher {
+    Http {
+        method: http::Method,
+        path: String,
+        query: Vec<(String, String)>,
+        body: Bytes,
+    },
+    #[cfg(feature = "jsonrpc")]
+    Rpc { method: String, params: Vec<Value> },
+    Any,
+}
+
+impl std::fmt::Display for Matcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Matcher::Http { method, path, .. } => write!(f, "{} {}", method, path),
+            #[cfg(feature = "jsonrpc")]
+            Matcher::Rpc { method, params } => write!(f, "rpc {}({})", method, Value::from(params.clone())),
+            Matcher::Any => f.write_str("any request"),
+        }
+    }
+}
+
+impl Matcher {
+    fn matches(&self, request: &http::Request<Bytes>) -> bool {
+        match self {
+            Matcher::Http { method, path, query, body } => {
+                request.method() == method
+                    && request.uri().path() == path
+                    && &query_pairs(request.uri().query()) == query
+                    && (body.is_empty() || same_body(body, request.body()))
+            }
+            #[cfg(feature = "jsonrpc")]
+            Matcher::Rpc { .. } => false,
+            Matcher::Any => true,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Answer {
+    Http(http::StatusCode, http::HeaderMap, Bytes),
+    #[cfg(feature = "jsonrpc")]
+    Rpc(Result<Value, JsonRpcError>),
+    Fn(Responder),
+}
+
+type ResponderFn = dyn Fn(&http::Request<Bytes>) -> http::Response<Bytes> + Send + Sync;
+
+struct Responder(Box<ResponderFn>);
+
+impl std::fmt::Debug for Responder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Responder")
+    }
+}
+
+impl Answer {
+    fn http_response(&self, request: &http::Request<Bytes>) -> Option<http::Response<Bytes>> {
+        match self {
+            Answer::Http(status, headers, body) => {
+                let mut response = http::Response::new(body.clone());
+                *response.status_mut() = *status;
+                *response.headers_mut() = headers.clone();
+                Some(response)
+            }
+            #[cfg(feature = "jsonrpc")]
+            Answer::Rpc(_) => None,
+            Answer::Fn(responder) => Some((responder.0)(request)),
+        }
+    }
+}
+
+/// Decoded query pairs, sorted so parameter order does not matter.
+fn query_pairs(query: Option<&str>) -> Vec<(String, String)> {
+    let mut pairs = serde_urlencoded::from_str::<Vec<(String, String)>>(query.unwrap_or_default()).unwrap_or_default();
+    pairs.sort();
+    pairs
+}
+
+fn same_body(expected: &[u8], actual: &[u8]) -> bool {
+    match (
+        serde_json::from_slice::<serde_json::Value>(expected),
+        serde_json::from_slice::<serde_json::Value>(actual),
+    ) {
+        (Ok(expected), Ok(actual)) => expected == actual,
+        _ => expected == actual,
+    }
+}
+
+fn answer(state: &State, request: &http::Request<Bytes>) -> http::Response<Bytes> {
+    let mocks = state.mocks.lock().unwrap().clone();
+    if let Some(mock) = mocks.iter().find(|mock| !mock.exhausted() && mock.matcher.matches(request)) {
+        mock.record(request);
+        if let Some(response) = mock.answer.http_response(request) {
+            return response;
+        }
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    if let Some(response) = answer_rpc(&mocks, request) {
+        return response;
+    }
+
+    let mut response = http::Response::new(Bytes::from(format!(
+        "no mock matches {} {}",
+        request.method(),
+        request.uri()
+    )));
+    *response.status_mut() = http::StatusCode::NOT_FOUND;
+    response
+}
+
+/// Answers each call of a JSON-RPC request or batch; `None` if the body is not JSON-RPC.
+#[cfg(feature = "jsonrpc")]
+fn answer_rpc(mocks: &[Arc<MockEntry>], request: &http::Request<Bytes>) -> Option<http::Response<Bytes>> {
+    let answer_call = |call: &Value| -> Option<Value> {
+        let method = call.get("method")?.as_str()?;
+        let params = call.get("params").and_then(Value::as_array).cloned().unwrap_or_default();
+        let mock = mocks.iter().find(|mock| {
+            !mock.exhausted() && matches!(&mock.matcher, Matcher::Rpc { method: m, params: p } if m == method && *p == params)
+        });
+        if let Some(mock) = mock {
+            mock.record(request);
+        }
+        let id = serde_json::from_value::<JsonRpcId>(call.get("id")?.clone()).unwrap_or(JsonRpcId::Null);
+        let answer = match mock.map(|mock| &mock.answer) {
+            Some(Answer::Rpc(answer)) => answer.clone(),
+            _ => Err(JsonRpcError::method_not_found(method)),
+        };
+        let result = match answer {
+            Ok(result) => JsonRpcResult::Value(JsonRpcResponse { id, jsonrpc: "2.0".into(), result }),
+            Err(error) => JsonRpcResult::Error(JsonRpcErrorResponse { jsonrpc: "2.0".into(), id, error }),
+        };
+        Some(serde_json::to_value(result).expect("serializable JSON-RPC answer"))
+    };
+
+    let body = match serde_json::from_slice::<Value>(request.body()).ok()? {
+        Value::Array(calls) if calls.iter().all(|call| call.get("method").is_some()) => {
+            Value::Array(calls.iter().filter_map(answer_call).collect())
+        }
+        call if call.get("method").is_some() => answer_call(&call).unwrap_or(Value::Null),
+        _ => return None,
+    };
+
+    let mut response = http::Response::new(Bytes::from(body.to_string()));
+    response
+        .headers_mut()
+        .insert(http::header::CONTENT_TYPE, http::HeaderValue::from_static("application/json"));
+    Some(response)
+}
+
+async fn serve(mut stream: TcpStream, state: Arc<State>) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+    // Answers may block (a `respond_with` responder simulating a slow backend), so keep them off
+    // the async workers serving the other connections.
+    let answering = state.clone();
+    let Ok((request, response)) = tokio::task::spawn_blocking(move || {
+        let response = answer(&answering, &request);
+        (request, response)
+    })
+    .await
+    else {
+        return;
+    };
+    state.requests.lock().unwrap().push(request);
+
+    let mut head = format!(
+        "HTTP/1.1 {} {}\r\n",
+        response.status().as_u16(),
+        response.status().canonical_reason().unwrap_or_default()
+    );
+    for (name, value) in response.headers() {
+        head.push_str(&format!("{}: {}\r\n", name, value.to_str().unwrap_or_default()));
+    }
+    head.push_str(&format!("content-length: {}\r\nconnection: close\r\n\r\n", response.body().len()));
+
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(response.body()).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<http::Request<Bytes>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next()?.split(' ');
+    let mut builder = http::Request::builder()
+        .method(request_line.next()?)
+        .uri(request_line.next()?);
+    let mut content_length = 0;
+    let mut chunked = false;
+    for (name, value) in lines.filter_map(|line| line.split_once(':')) {
+        let (name, value) = (name.trim(), value.trim());
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse::<usize>().ok()?;
+        }
+        if name.eq_ignore_ascii_case("transfer-encoding") && value.eq_ignore_ascii_case("chunked") {
+            chunked = true;
+        }
+        builder = builder.header(name, value);
+    }
+
+    let mut body = buf[header_end..].to_vec();
+    if chunked {
+        loop {
+            if let Some(decoded) = decode_chunked(&body) {
+                return builder.body(Bytes::from(decoded)).ok();
+            }
+            let n = stream.read(&mut chunk).await.ok()?;
+            if n == 0 {
+                return None;
+            }
+            body.extend_from_slice(&chunk[..n]);
+        }
+    }
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    builder.body(Bytes::from(body)).ok()
+}
+
+/// Decodes a `transfer-encoding: chunked` body, or `None` until all of it has arrived.
+fn decode_chunked(mut raw: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = raw.windows(2).position(|w| w == b"\r\n")?;
+        let size = std::str::from_utf8(&raw[..line_end]).ok()?.split(';').next()?.trim();
+        let size = usize::from_str_radix(size, 16).ok()?;
+        raw = &raw[line_end + 2..];
+        if size == 0 {
+            // trailers, if any, end with an empty line
+            return raw.windows(2).any(|w| w == b"\r\n").then_some(body);
+        }
+        if raw.len() < size + 2 {
+            return None;
+        }
+        body.extend_from_slice(&raw[..size]);
+        raw = &raw[size + 2..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        provider::{JsonProviderType, ProviderType},
+        test_util::TestTarget,
+    };
+    use serde_json::json;
+
+    fn get_item(id: u32) -> TestTarget {
+        TestTarget::get("https://shop.example/api", format!("/items/{}", id))
+            .with_query("currency", "eur")
+            .with_bearer("secret")
+    }
+
+    fn place_order(item: u32, quantity: u32) -> TestTarget {
+        TestTarget::post("https://shop.example/api", "/orders")
+            .with_bearer("secret")
+            .with_json(json!({ "item": item, "quantity": quantity }))
+    }
+
+    #[tokio::test]
+    async fn test_expectations() {
+        let server = MockServer::start().await;
+        let item = server.expect(get_item(1)).respond_json(json!({ "price": 10 })).times(1);
+        let order = server.expect(place_order(1, 2)).respond_status(201);
+        let provider: Provider<TestTarget> = server.provider();
+
+        let body: serde_json::Value = provider.request_json(get_item(1)).await.unwrap();
+        assert_eq!(body["price"], 10);
+        assert_eq!(item.requests()[0].headers()["authorization"], "Bearer secret");
+
+        let response = provider.request(place_order(1, 2)).await.unwrap();
+        assert_eq!(response.status(), 201);
+        assert_eq!(order.calls(), 1);
+
+        let response = provider.request(place_order(1, 3)).await.unwrap();
+        assert_eq!(response.status(), 404);
+        assert_eq!(order.calls(), 1);
+        assert_eq!(server.received_requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_times_sequences_answers_and_verifies() {
+        let server = MockServer::start().await;
+        server.expect(get_item(2)).respond_status(503).times(1);
+        server.expect(get_item(2)).respond_json(json!({ "price": 20 }));
+        let provider: Provider<TestTarget> = server.provider();
+
+        assert_eq!(provider.request(get_item(2)).await.unwrap().status(), 503);
+        assert_eq!(provider.request(get_item(2)).await.unwrap().status(), 200);
+
+        server.expect(get_item(3)).respond_status(200).times(1);
+        let verified = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| server.verify()));
+        assert!(verified.is_err());
+        provider.request(get_item(3)).await.unwrap();
+        server.verify();
+    }
+
+    #[tokio::test]
+    async fn test_respond_with_answers_unmatched_requests() {
+        let server = MockServer::start().await;
+        server.expect(get_item(1)).respond_status(201);
+        let fallback = server.respond_with(|request| http::Response::new(Bytes::from(request.uri().path().to_string())));
+        let provider: Provider<TestTarget> = server.provider();
+
+        assert_eq!(provider.request(get_item(1)).await.unwrap().status(), 201);
+        let body = provider.request(get_item(2)).await.unwrap().text().await.unwrap();
+        assert_eq!(body, "/items/2");
+        assert_eq!(fallback.calls(), 1);
+    }
+
+    #[cfg(feature = "jsonrpc")]
+    #[tokio::test]
+    async fn test_rpc_expectations() {
+        use crate::{jsonrpc::DynamicRpc, provider::JsonRpcProviderType};
+
+        let server = MockServer::start().await;
+        server.expect_rpc(DynamicRpc::new("", "eth_chainId", vec![])).respond_result("0x1");
+        server
+            .expect_rpc(DynamicRpc::new("", "eth_getBalance", vec![json!("0xabc")]))
+            .respond_error(JsonRpcError { code: -32000, message: "unknown account".into() });
+        let provider: Provider<DynamicRpc> = server.provider();
+
+        let results = provider
+            .batch::<String>(vec![
+                DynamicRpc::new("", "eth_chainId", vec![]),
+                DynamicRpc::new("", "eth_getBalance", vec![json!("0xabc")]),
+                DynamicRpc::new("", "eth_gasPrice", vec![]),
+            ])
+            .await
+            .unwrap();
+        assert!(matches!(&results[0], JsonRpcResult::Value(response) if response.result == "0x1"));
+        assert!(matches!(&results[1], JsonRpcResult::Error(response) if response.error.code == -32000));
+        assert!(matches!(&results[2], JsonRpcResult::Error(response) if response.error.code == -32601));
+    }
+}
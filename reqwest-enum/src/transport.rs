@@ -0,0 +1,236 @@
+//! Transports carry a built request to the server and bring back its response, decoupling
+//! `Provider` from a particular HTTP client.
+use crate::{error::Error, http::HTTPResponse};
+use bytes::Bytes;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+/// Response body returned by a `Transport`; anything `Into<reqwest::Body>` (bytes, strings, streams) works.
+pub type Body = reqwest::Body;
+
+/// Executes HTTP requests for a `Provider`.
+///
+/// Implement this to send requests through something other than `reqwest`, e.g. a custom
+/// hyper client, a WebSocket or IPC carrier, or a canned in-memory responder for tests.
+/// The provider's timeout travels in the request extensions, where `reqwest` keeps it, and is
+/// honoured by the bundled reqwest transports.
+pub trait Transport: Send + Sync + 'static {
+    fn execute(&self, request: http::Request<Bytes>) -> impl Future<Output = Result<http::Response<Body>, Error>> + Send;
+
+    /// Executes a request built by the provider's `reqwest` request builder. By default it is
+    /// buffered into an `http::Request<Bytes>` for `execute`, which fails for streaming bodies;
+    /// the reqwest transports send it as is.
+    fn execute_reqwest(
+        &self,
+        request: reqwest::Request,
+    ) -> impl Future<Output = Result<http::Response<Body>, Error>> + Send {
+        async move { self.execute(from_reqwest_request(request)?).await }
+    }
+}
+
+/// The transport `Provider` uses unless another one is set with `Provider::with_transport`
+/// or `Provider::with_middleware_client`.
+pub type DefaultTransport = ReqwestTransport;
+
+/// Sends requests with a `reqwest::Client`.
+#[derive(Debug, Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    async fn execute(&self, request: http::Request<Bytes>) -> Result<http::Response<Body>, Error> {
+        self.execute_reqwest(reqwest::Request::try_from(request)?).await
+    }
+
+    async fn execute_reqwest(&self, request: reqwest::Request) -> Result<http::Response<Body>, Error> {
+        let response = self.client.execute(request).await?;
+        Ok(into_http_response(response))
+    }
+}
+
+/// Sends requests through a `reqwest_middleware::ClientWithMiddleware`, passing the request's
+/// extensions (see `ProviderRequestBuilder::with_extension`) to its middleware.
+#[cfg(feature = "middleware")]
+#[derive(Debug, Clone)]
+pub struct MiddlewareTransport {
+    client: reqwest_middleware::ClientWithMiddleware,
+}
+
+#[cfg(feature = "middleware")]
+impl MiddlewareTransport {
+    pub fn new(client: reqwest_middleware::ClientWithMiddleware) -> Self {
+        Self { client }
+    }
+}
+
+#[cfg(feature = "middleware")]
+impl Default for MiddlewareTransport {
+    fn default() -> Self {
+        Self::new(reqwest_middleware::ClientBuilder::new(reqwest::Client::new()).build())
+    }
+}
+
+#[cfg(feature = "middleware")]
+impl Transport for MiddlewareTransport {
+    async fn execute(&self, request: http::Request<Bytes>) -> Result<http::Response<Body>, Error> {
+        self.execute_reqwest(reqwest::Request::try_from(request)?).await
+    }
+
+    async fn execute_reqwest(&self, request: reqwest::Request) -> Result<http::Response<Body>, Error> {
+        // reqwest keeps extensions private; the `http` round trip moves the body, streams included
+        let request = http::Request::<reqwest::Body>::try_from(request)?;
+        let mut extensions = request.extensions().clone();
+        let request = reqwest::Request::try_from(request)?;
+        let response = self.client.execute_with_extensions(request, &mut extensions).await?;
+        Ok(into_http_response(response))
+    }
+}
+
+type Responder = dyn Fn(&http::Request<Bytes>) -> http::Response<Bytes> + Send + Sync;
+
+/// Answers requests with a closure instead of the network, and records every request it receives.
+/// Clones share the same responder and recorded requests.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let transport = InMemoryTransport::new(|_request| http::Response::new(Bytes::from_static(b"{\"ok\":true}")));
+/// let provider = Provider::<HttpBin>::default().with_transport(transport.clone());
+/// provider.request(HttpBin::Get).await?;
+/// assert_eq!(transport.requests()[0].uri(), "https://httpbin.org/get");
+/// ```
+#[derive(Clone)]
+pub struct InMemoryTransport {
+    responder: Arc<Responder>,
+    requests: Arc<Mutex<Vec<http::Request<Bytes>>>>,
+}
+
+impl std::fmt::Debug for InMemoryTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InMemoryTransport")
+            .field("responder", &"<function>")
+            .field("requests", &self.requests.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl InMemoryTransport {
+    pub fn new<F>(responder: F) -> Self
+    where
+        F: Fn(&http::Request<Bytes>) -> http::Response<Bytes> + Send + Sync + 'static,
+    {
+        Self {
+            responder: Arc::new(responder),
+            requests: Arc::default(),
+        }
+    }
+
+    /// The requests received so far, oldest first (without extensions).
+    pub fn requests(&self) -> Vec<http::Request<Bytes>> {
+        self.requests.lock().unwrap().iter().map(copy_request).collect()
+    }
+}
+
+impl Transport for InMemoryTransport {
+    async fn execute(&self, request: http::Request<Bytes>) -> Result<http::Response<Body>, Error> {
+        let response = (self.responder)(&request);
+        self.requests.lock().unwrap().push(request);
+        Ok(response.map(Body::from))
+    }
+}
+
+pub(crate) fn copy_request(request: &http::Request<Bytes>) -> http::Request<Bytes> {
+    let mut copy = http::Request::new(request.body().clone());
+    *copy.method_mut() = request.method().clone();
+    *copy.uri_mut() = request.uri().clone();
+    *copy.version_mut() = request.version();
+    *copy.headers_mut() = request.headers().clone();
+    copy
+}
+
+/// Executes a request built by the provider's `reqwest` request builder on `transport`.
+pub(crate) async fn execute_reqwest<X: Transport>(transport: &X, request: reqwest::Request) -> Result<HTTPResponse, Error> {
+    let response = transport.execute_reqwest(request).await?;
+    Ok(into_reqwest_response(response))
+}
+
+/// Converts a request built by the provider's `reqwest` request builder for a `Transport`.
+/// Extensions (including the timeout) are kept.
+pub(crate) fn from_reqwest_request(request: reqwest::Request) -> Result<http::Request<Bytes>, Error> {
+    let body = match request.body() {
+        None => Bytes::new(),
+        Some(body) => match body.as_bytes() {
+            Some(bytes) => Bytes::copy_from_slice(bytes),
+            None => return Err(Error::Transport("streaming request bodies are not supported".into())),
+        },
+    };
+    Ok(http::Request::<reqwest::Body>::try_from(request)?.map(|_| body))
+}
+
+/// Converts a `Transport` response back into the `HTTPResponse` returned by `Provider`.
+fn into_reqwest_response(response: http::Response<Body>) -> HTTPResponse {
+    HTTPResponse::from(response)
+}
+
+/// Converts a `reqwest` response for a `Transport`, keeping its final URL.
+fn into_http_response(response: reqwest::Response) -> http::Response<Body> {
+    use reqwest::ResponseBuilderExt;
+
+    let url = response.url().clone();
+    let (parts, body) = http::Response::<Body>::from(response).into_parts();
+    let mut builder = http::Response::builder()
+        .status(parts.status)
+        .version(parts.version)
+        .url(url);
+    if let Some(headers) = builder.headers_mut() {
+        *headers = parts.headers;
+    }
+    let mut converted = builder.body(body).expect("parts of a valid response");
+    converted.extensions_mut().extend(parts.extensions);
+    converted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_in_memory_transport_records_requests() {
+        let transport = InMemoryTransport::new(|request| {
+            let mut response = http::Response::new(request.body().clone());
+            *response.status_mut() = http::StatusCode::CREATED;
+            response
+        });
+        let request = http::Request::post("http://example.com/echo").body(Bytes::from_static(b"hello")).unwrap();
+
+        let response = into_reqwest_response(transport.execute(request).await.unwrap());
+        assert_eq!(response.status(), 201);
+        assert_eq!(response.text().await.unwrap(), "hello");
+
+        let requests = transport.clone().requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method(), http::Method::POST);
+        assert_eq!(requests[0].uri(), "http://example.com/echo");
+    }
+
+    #[test]
+    fn test_from_reqwest_request_keeps_timeout() {
+        let mut request = reqwest::Request::new(reqwest::Method::GET, "http://example.com/".parse().unwrap());
+        *request.timeout_mut() = Some(std::time::Duration::from_secs(3));
+        *request.body_mut() = Some("body".into());
+
+        let converted = from_reqwest_request(request).unwrap();
+        assert_eq!(converted.body(), &Bytes::from_static(b"body"));
+        let back = reqwest::Request::try_from(converted.map(reqwest::Body::from)).unwrap();
+        assert_eq!(back.timeout(), Some(&std::time::Duration::from_secs(3)));
+    }
+}
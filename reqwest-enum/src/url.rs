@@ -0,0 +1,81 @@
+//! URL construction shared by `Target::absolute_url`, `Provider::request_url` and every request.
+use crate::{error::Error, path::has_dot_segment};
+use reqwest::Url;
+
+/// Joins `base_url` and `path` with exactly one slash between them, then appends the query
+/// from `path` (if any) followed by `query`, an already encoded query string.
+///
+/// Fails with `Error::InvalidUrl` if the base URL is not an absolute `http(s)`-like URL, or if
+/// `path` contains a dot-segment (`.` or `..`) that would change the route.
+///
+/// ```rust,ignore
+/// let url = build_url("https://api.example/v1/", "/users?active=true", "page=2")?;
+/// assert_eq!(url.as_str(), "https://api.example/v1/users?active=true&page=2");
+/// ```
+pub fn build_url(base_url: &str, path: &str, query: &str) -> Result<Url, Error> {
+    let invalid = |reason: String| Error::InvalidUrl {
+        url: format!("{}{}", base_url, path),
+        reason,
+    };
+
+    let mut url = Url::parse(base_url).map_err(|e| invalid(e.to_string()))?;
+    if url.cannot_be_a_base() {
+        return Err(invalid("not a base URL".into()));
+    }
+
+    let (path, path_query) = match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    };
+    if has_dot_segment(path) {
+        return Err(invalid("path contains a dot-segment".into()));
+    }
+    if !path.is_empty() {
+        let joined = format!("{}/{}", url.path().trim_end_matches('/'), path.trim_start_matches('/'));
+        url.set_path(&joined);
+    }
+
+    let query = [url.query(), path_query, Some(query)]
+        .into_iter()
+        .flatten()
+        .flat_map(|query| query.split('&'))
+        .filter(|pair| !pair.is_empty())
+        .collect::<Vec<_>>()
+        .join("&");
+    url.set_query((!query.is_empty()).then_some(query.as_str()));
+    Ok(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_url() {
+        let cases = [
+            ("https://a.example", "/get", "", "https://a.example/get"),
+            ("https://a.example/", "get", "", "https://a.example/get"),
+            ("https://a.example/v1/", "/users", "x=1", "https://a.example/v1/users?x=1"),
+            ("https://a.example/v1", "users/", "", "https://a.example/v1/users/"),
+            ("https://a.example", "/get?ts=1", "x=1", "https://a.example/get?ts=1&x=1"),
+            ("https://a.example?key=k", "/get?", "x=1", "https://a.example/get?key=k&x=1"),
+            ("https://a.example/rpc", "", "", "https://a.example/rpc"),
+            ("https://a.example", "/a b", "q=a+b", "https://a.example/a%20b?q=a+b"),
+        ];
+        for (base_url, path, query, expected) in cases {
+            assert_eq!(build_url(base_url, path, query).unwrap().as_str(), expected);
+        }
+    }
+
+    #[test]
+    fn test_invalid_url() {
+        for base_url in ["not a url", "/relative", "mailto:someone@example.com"] {
+            let err = build_url(base_url, "/path", "").unwrap_err();
+            assert!(matches!(err, Error::InvalidUrl { .. }), "{}", err);
+        }
+        assert_eq!(
+            build_url("", "/path", "").unwrap_err().to_string(),
+            "Invalid URL /path: relative URL without a base"
+        );
+    }
+}